@@ -0,0 +1,795 @@
+// oxidf catalog of the available operations
+// Author: David Issa Mattos
+// Mantainer: David Issa Mattos
+
+/// Description of an operation, shown by the ops command
+pub struct OperationInfo {
+    pub name: &'static str,
+    // input, operation, output or pipeline
+    pub kind: &'static str,
+    // does the operation collect the data of the previous steps?
+    pub collects: bool,
+    pub description: &'static str,
+    pub properties: &'static [PropertyInfo],
+    pub example: &'static str,
+}
+
+/// Description of a property of an operation
+pub struct PropertyInfo {
+    pub name: &'static str,
+    pub required: bool,
+    pub description: &'static str,
+}
+
+const fn required(name: &'static str, description: &'static str) -> PropertyInfo {
+    PropertyInfo {
+        name,
+        required: true,
+        description,
+    }
+}
+
+const fn optional(name: &'static str, description: &'static str) -> PropertyInfo {
+    PropertyInfo {
+        name,
+        required: false,
+        description,
+    }
+}
+
+pub const CATALOG: &[OperationInfo] = &[
+    // Input
+    OperationInfo {
+        name: "read_csv",
+        kind: "input",
+        collects: false,
+        description: "Read a csv file lazily",
+        properties: &[
+            required("path", "path to the csv file, which can be compressed with gzip, zstd or bzip2"),
+            required("delimiter", "single character separating the fields"),
+            required("header", "does the csv include a header?"),
+            optional("skip_rows", "number of lines skipped before the header"),
+            optional("comment", "character starting the lines that are skipped, e.g. #"),
+            optional("quote", "character quoting the fields, \" by default, \"\" to read quotes as text"),
+            optional("null_values", "string or list of strings read as empty values, e.g. [\"N/A\", \"\"]"),
+            optional("encoding", "utf8 (default) or lossy, replacing the invalid characters"),
+            optional("dtypes", "table of columns and their types, instead of inferring them"),
+            optional("infer_schema_length", "rows used to infer the types, 100 by default, 0 for all the rows"),
+            optional("columns", "column or list of columns read"),
+            optional("n_rows", "number of rows read"),
+            optional("parse_dates", "parse the columns that look like dates and datetimes"),
+            optional("ignore_errors", "values that cannot be parsed are empty, true by default"),
+            optional("decimal_comma", "read the numbers written with a decimal comma, e.g. 1.234,5"),
+            optional("quarantine", "csv file where the rows that cannot be read are written instead of reading them with empty values"),
+            optional("max_rejected", "fraction of rejected rows above which the pipeline fails, e.g. 0.05"),
+        ],
+        example: r#"[[steps]]
+    operation = "read_csv"
+    [steps.properties]
+    path = "./tests/data/iris.csv"
+    delimiter = ","
+    header = true"#,
+    },
+    // Operations
+    OperationInfo {
+        name: "rename",
+        kind: "operation",
+        collects: false,
+        description: "Rename a single column",
+        properties: &[
+            required("col", "column to rename"),
+            required("name", "new name of the column"),
+        ],
+        example: r#"[[steps]]
+    operation = "rename"
+    [steps.properties]
+    col = "Sepal.Width"
+    name = "Width""#,
+    },
+    OperationInfo {
+        name: "select",
+        kind: "operation",
+        collects: false,
+        description: "Select multiple columns",
+        properties: &[required("columns", "list of columns to keep")],
+        example: r#"[[steps]]
+    operation = "select"
+    [steps.properties]
+    columns = ["Sepal.Length", "Sepal.Width"]"#,
+    },
+    OperationInfo {
+        name: "filter_eq",
+        kind: "operation",
+        collects: false,
+        description: "Keep the rows where a column is equal to a value",
+        properties: &[
+            required("col", "column to filter"),
+            required("value", "string or number"),
+        ],
+        example: r#"[[steps]]
+    operation = "filter_eq"
+    [steps.properties]
+    col = "Species"
+    value = "versicolor""#,
+    },
+    OperationInfo {
+        name: "filter_gt",
+        kind: "operation",
+        collects: false,
+        description: "Keep the rows where a column is greater than a value",
+        properties: &[
+            required("col", "column to filter"),
+            required("value", "number"),
+        ],
+        example: r#"[[steps]]
+    operation = "filter_gt"
+    [steps.properties]
+    col = "Sepal.Width"
+    value = 3.0"#,
+    },
+    OperationInfo {
+        name: "filter_gt_eq",
+        kind: "operation",
+        collects: false,
+        description: "Keep the rows where a column is greater than or equal to a value",
+        properties: &[
+            required("col", "column to filter"),
+            required("value", "number"),
+        ],
+        example: r#"[[steps]]
+    operation = "filter_gt_eq"
+    [steps.properties]
+    col = "Sepal.Width"
+    value = 3.0"#,
+    },
+    OperationInfo {
+        name: "filter_lt",
+        kind: "operation",
+        collects: false,
+        description: "Keep the rows where a column is lesser than a value",
+        properties: &[
+            required("col", "column to filter"),
+            required("value", "number"),
+        ],
+        example: r#"[[steps]]
+    operation = "filter_lt"
+    [steps.properties]
+    col = "Sepal.Width"
+    value = 3.0"#,
+    },
+    OperationInfo {
+        name: "filter_lt_eq",
+        kind: "operation",
+        collects: false,
+        description: "Keep the rows where a column is lesser than or equal to a value",
+        properties: &[
+            required("col", "column to filter"),
+            required("value", "number"),
+        ],
+        example: r#"[[steps]]
+    operation = "filter_lt_eq"
+    [steps.properties]
+    col = "Sepal.Width"
+    value = 3.0"#,
+    },
+    OperationInfo {
+        name: "filter_isin",
+        kind: "operation",
+        collects: false,
+        description: "Keep the rows where a column is in a list of values",
+        properties: &[
+            required("col", "column to filter"),
+            required(
+                "value_list",
+                "non-empty list of strings, integers or floats (all of the same type)",
+            ),
+        ],
+        example: r#"[[steps]]
+    operation = "filter_isin"
+    [steps.properties]
+    col = "Species"
+    value_list = ["setosa", "versicolor"]"#,
+    },
+    OperationInfo {
+        name: "filter_contains",
+        kind: "operation",
+        collects: false,
+        description: "Keep the rows where a string column matches a regex pattern",
+        properties: &[
+            required("col", "column to filter"),
+            required("value", "regex pattern"),
+        ],
+        example: r#"[[steps]]
+    operation = "filter_contains"
+    [steps.properties]
+    col = "Species"
+    value = "^set.*""#,
+    },
+    OperationInfo {
+        name: "remove_na",
+        kind: "operation",
+        collects: false,
+        description: "Remove the rows where a column is NA",
+        properties: &[required("col", "column to check, or \"*\" for all columns")],
+        example: r#"[[steps]]
+    operation = "remove_na"
+    [steps.properties]
+    col = "Species""#,
+    },
+    OperationInfo {
+        name: "recode",
+        kind: "operation",
+        collects: false,
+        description: "Recode the values of a column",
+        properties: &[
+            required("col", "column to recode"),
+            required("from", "list of values to replace (not needed with lookup)"),
+            required("to", "list of new values, in the same order as from (not needed with lookup)"),
+            optional("lookup", "CSV file with the values in its first column and the new values in the second"),
+            optional("unmatched", "values that are not recoded: keep (default), null, default or error"),
+            optional("default", "new value of the values that are not recoded, when unmatched is default"),
+        ],
+        example: r#"[[steps]]
+    operation = "recode"
+    [steps.properties]
+    col = "Survived"
+    from = ["Yes", "No"]
+    to = [1, 0]"#,
+    },
+    OperationInfo {
+        name: "cast",
+        kind: "operation",
+        collects: false,
+        description: "Change the type of one or more columns",
+        properties: &[
+            required("col", "column or list of columns to cast"),
+            required(
+                "to",
+                "string, int, float, int8-int64, uint8-uint64, float32, float64, bool, categorical, date, datetime or duration",
+            ),
+            optional("unit", "time unit of datetime and duration: ms, us (default) or ns"),
+            optional("format", "format of the dates and datetimes in strings, e.g. %d/%m/%Y"),
+            optional("strict", "fail when a value cannot be converted, instead of making it null"),
+        ],
+        example: r#"[[steps]]
+    operation = "cast"
+    [steps.properties]
+    col = "Sepal.Length"
+    to = "int""#,
+    },
+    OperationInfo {
+        name: "anonymize",
+        kind: "operation",
+        collects: false,
+        description: "Hash the values of one or more columns (pseudonymization)",
+        properties: &[
+            required(
+                "col",
+                "column or list of columns to hash. Columns that are not strings are hashed as text",
+            ),
+            optional("algorithm", "sha256 (default), sha512 or blake3"),
+            optional("salt", "text added before every value"),
+            optional("key_file", "file with a secret key, to hash with HMAC"),
+            optional(
+                "key_env",
+                "environment variable with a secret key, to hash with HMAC",
+            ),
+            optional("length", "number of characters kept from the hash"),
+            optional(
+                "mapping",
+                "csv file where the original values and their hashes are saved",
+            ),
+        ],
+        example: r#"[[steps]]
+    operation = "anonymize"
+    [steps.properties]
+    col = ["CarID"]
+    algorithm = "sha256"
+    key_env = "OXIDF_KEY"
+    length = 16"#,
+    },
+    OperationInfo {
+        name: "mask_keep_last",
+        kind: "operation",
+        collects: false,
+        description: "Mask all the characters of a column except the last ones",
+        properties: &[
+            required("col", "column to mask"),
+            required("keep", "number of characters kept at the end"),
+            optional("mask_char", "character replacing the masked ones, * by default"),
+        ],
+        example: r#"[[steps]]
+    operation = "mask_keep_last"
+    [steps.properties]
+    col = "CustomerID"
+    keep = 4"#,
+    },
+    OperationInfo {
+        name: "mask_email",
+        kind: "operation",
+        collects: false,
+        description: "Mask the part of e-mail addresses before the @",
+        properties: &[
+            required("col", "column to mask"),
+            optional("keep", "number of characters kept at the start, 0 by default"),
+            optional("mask_char", "character replacing the masked ones, * by default"),
+        ],
+        example: r#"[[steps]]
+    operation = "mask_email"
+    [steps.properties]
+    col = "Email"
+    keep = 1"#,
+    },
+    OperationInfo {
+        name: "mask_regex",
+        kind: "operation",
+        collects: false,
+        description: "Replace every match of a regex pattern in a column",
+        properties: &[
+            required("col", "column to mask"),
+            required("pattern", "regex pattern of the text to replace"),
+            optional("replacement", "text replacing the matches, *** by default"),
+        ],
+        example: r#"[[steps]]
+    operation = "mask_regex"
+    [steps.properties]
+    col = "Notes"
+    pattern = "\\+?\\d[\\d ]{6,}\\d"
+    replacement = "[phone]""#,
+    },
+    OperationInfo {
+        name: "mask_range",
+        kind: "operation",
+        collects: false,
+        description: "Generalize a numeric column into ranges, e.g. 43 -> \"[40, 50)\"",
+        properties: &[
+            required("col", "column to mask"),
+            required("width", "width of the ranges"),
+            optional("origin", "start of one of the ranges, 0 by default"),
+        ],
+        example: r#"[[steps]]
+    operation = "mask_range"
+    [steps.properties]
+    col = "Age"
+    width = 10"#,
+    },
+    OperationInfo {
+        name: "mask_date",
+        kind: "operation",
+        collects: false,
+        description: "Coarsen a date column to its month or year",
+        properties: &[
+            required("col", "column to mask"),
+            required("to", "month or year"),
+            optional(
+                "format",
+                "format of the dates in string columns, %Y-%m-%d by default",
+            ),
+        ],
+        example: r#"[[steps]]
+    operation = "mask_date"
+    [steps.properties]
+    col = "Birthdate"
+    to = "year""#,
+    },
+    OperationInfo {
+        name: "pivot",
+        kind: "operation",
+        collects: true,
+        description: "Pivot a dataframe from long to wide. Collects the data",
+        properties: &[
+            required("values", "columns with the values of the new columns"),
+            required("index", "columns identifying the rows"),
+            required("columns", "columns with the names of the new columns"),
+            required(
+                "aggregation",
+                "first, last, max, mean, median, min, sum or count",
+            ),
+            required("sort_columns", "sort the new columns by name"),
+        ],
+        example: r#"[[steps]]
+    operation = "pivot"
+    [steps.properties]
+    values = ["parvalue"]
+    index = ["CarID", "ts", "request"]
+    columns = ["parname"]
+    aggregation = "first"
+    sort_columns = true"#,
+    },
+    OperationInfo {
+        name: "unique",
+        kind: "operation",
+        collects: false,
+        description: "Keep only the first of the duplicated rows",
+        properties: &[optional(
+            "cols",
+            "columns used to find duplicates (all columns if not given)",
+        )],
+        example: r#"[[steps]]
+    operation = "unique"
+    [steps.properties]
+    cols = ["Species"]"#,
+    },
+    OperationInfo {
+        name: "sample",
+        kind: "operation",
+        collects: true,
+        description: "Random sample of the rows. Collects the data",
+        properties: &[
+            optional("n", "number of rows drawn (in every stratum if stratified)"),
+            optional(
+                "fraction",
+                "fraction of the rows drawn (in every stratum if stratified), instead of n",
+            ),
+            optional("replace", "draw with replacement, false by default"),
+            optional("seed", "seed of the random generator, for reproducible samples"),
+            optional("stratify", "column whose values are sampled separately"),
+        ],
+        example: r#"[[steps]]
+    operation = "sample"
+    [steps.properties]
+    n = 5
+    seed = 42
+    stratify = "Species""#,
+    },
+    OperationInfo {
+        name: "head",
+        kind: "operation",
+        collects: false,
+        description: "Keep the first rows, or the first rows of every group",
+        properties: &[
+            required("n", "number of rows kept"),
+            optional("by", "column or list of columns of the groups"),
+        ],
+        example: r#"[[steps]]
+    operation = "head"
+    [steps.properties]
+    n = 2
+    by = "Species""#,
+    },
+    OperationInfo {
+        name: "tail",
+        kind: "operation",
+        collects: false,
+        description: "Keep the last rows, or the last rows of every group",
+        properties: &[
+            required("n", "number of rows kept"),
+            optional("by", "column or list of columns of the groups"),
+        ],
+        example: r#"[[steps]]
+    operation = "tail"
+    [steps.properties]
+    n = 10"#,
+    },
+    OperationInfo {
+        name: "slice",
+        kind: "operation",
+        collects: false,
+        description: "Keep the rows starting at an offset",
+        properties: &[
+            required("offset", "first row kept, negative to count from the end"),
+            optional("length", "number of rows kept, all the remaining rows if not given"),
+        ],
+        example: r#"[[steps]]
+    operation = "slice"
+    [steps.properties]
+    offset = 100
+    length = 50"#,
+    },
+    OperationInfo {
+        name: "window",
+        kind: "operation",
+        collects: false,
+        description: "Compute a window function in a new column, optionally for every group of rows",
+        properties: &[
+            required(
+                "function",
+                "rank, row_number, cum_sum, cum_min, cum_max, cum_count, lag, lead, diff or pct_change",
+            ),
+            required("col", "column of the function (not needed by row_number)"),
+            optional("name", "name of the new column, {col}_{function} by default"),
+            optional("n", "rows of lag, lead, diff and pct_change, 1 by default"),
+            optional("method", "rank method: dense (default), min, max, average or ordinal"),
+            optional("descending", "rank from the largest value"),
+            optional("by", "column or list of columns of the groups"),
+            optional("order_by", "column giving the order of the rows"),
+            optional("order_descending", "order the rows from the largest value of order_by"),
+        ],
+        example: r#"[[steps]]
+    operation = "window"
+    [steps.properties]
+    function = "lag"
+    col = "parvalue"
+    by = "CarID"
+    order_by = "ts""#,
+    },
+    OperationInfo {
+        name: "rolling",
+        kind: "operation",
+        collects: false,
+        description: "Compute a rolling aggregation in a new column, over a number of rows or a time duration",
+        properties: &[
+            required("function", "mean, sum, min, max, std or median"),
+            required("col", "column aggregated"),
+            required("window", "number of rows, or a duration such as 30s, 5m, 1h or 2d"),
+            optional("on", "date or datetime column of the duration windows"),
+            optional("format", "format of on when it is a string, e.g. %Y-%m-%d %H:%M:%S"),
+            optional("name", "name of the new column, {col}_rolling_{function} by default"),
+            optional("min_periods", "values needed in the window to compute a result, 1 by default"),
+            optional("center", "center the window on the row instead of ending it on the row"),
+            optional("by", "column or list of columns of the groups"),
+        ],
+        example: r#"[[steps]]
+    operation = "rolling"
+    [steps.properties]
+    function = "mean"
+    col = "speed"
+    window = "5m"
+    on = "ts"
+    by = "CarID""#,
+    },
+    OperationInfo {
+        name: "resample",
+        kind: "operation",
+        collects: false,
+        description: "Aggregate the values in intervals of a date or datetime column. Collects the data when filling the intervals without rows",
+        properties: &[
+            required("on", "date or datetime column grouped in intervals"),
+            required("every", "duration of the intervals, such as 30s, 5m, 1h or 1d"),
+            required(
+                "aggregations",
+                "table of columns and their aggregation (or list of aggregations): first, last, max, mean, median, min, sum, count or std",
+            ),
+            optional("format", "format of on when it is a string, e.g. %Y-%m-%d %H:%M:%S"),
+            optional("by", "column or list of columns of the groups"),
+            optional("fill", "intervals without rows: none (default, left out), null, forward or interpolate"),
+        ],
+        example: r#"[[steps]]
+    operation = "resample"
+    [steps.properties]
+    on = "ts"
+    every = "5m"
+    by = "CarID"
+    fill = "forward"
+    [steps.properties.aggregations]
+    speed = "mean""#,
+    },
+    OperationInfo {
+        name: "bin",
+        kind: "operation",
+        collects: false,
+        description: "Cut a numeric column in intervals, writing the interval of every value in a new column",
+        properties: &[
+            required("col", "numeric column cut in intervals"),
+            optional("breaks", "list of increasing breaks between the intervals"),
+            optional("bins", "number of intervals of the same width between the minimum and maximum"),
+            optional("quantiles", "number of quantile intervals, or list of probabilities of the breaks"),
+            optional("labels", "list of labels of the intervals, e.g. [\"low\", \"high\"]"),
+            optional("closed", "side of the intervals that is closed: right (default) or left"),
+            optional("dtype", "type of the new column: string (default) or categorical"),
+            optional("name", "name of the new column, {col}_bin by default"),
+            optional("precision", "decimals of the breaks in the labels, 3 by default"),
+        ],
+        example: r#"[[steps]]
+    operation = "bin"
+    [steps.properties]
+    col = "Sepal.Length"
+    breaks = [4, 5, 6.5, 8]
+    labels = ["small", "medium", "large"]"#,
+    },
+    OperationInfo {
+        name: "encode",
+        kind: "operation",
+        collects: false,
+        description: "Encode categorical columns as numbers: one-hot, ordinal or frequency",
+        properties: &[
+            required("col", "column or list of columns encoded"),
+            required("method", "onehot, ordinal or frequency"),
+            optional("prefix", "prefix of the one-hot columns, {col}_ by default"),
+            optional("drop_first", "leave out the one-hot column of the first category"),
+            optional("order", "list of categories in order, coded from 0 (ordinal)"),
+            optional("mapping", "CSV file where the categories and their codes are saved"),
+            optional("load_mapping", "CSV file of categories and codes saved by an earlier run, used instead of the data"),
+        ],
+        example: r#"[[steps]]
+    operation = "encode"
+    [steps.properties]
+    col = ["Sex", "Age"]
+    method = "onehot"
+    drop_first = true
+    mapping = "./codes.csv""#,
+    },
+    // Summary
+    OperationInfo {
+        name: "describe",
+        kind: "operation",
+        collects: true,
+        description: "Descriptive statistics of every column, as a frame with one row per column. Collects the data",
+        properties: &[
+            optional("columns", "column or list of columns described, all by default"),
+            optional("by", "column or list of columns of the groups described separately"),
+            optional("top", "number of most frequent values of the non-numeric columns, 3 by default"),
+            optional("print", "print the statistics in the terminal"),
+        ],
+        example: r#"[[steps]]
+    operation = "describe"
+    [steps.properties]
+    by = "Species"
+    print = true"#,
+    },
+    OperationInfo {
+        name: "value_counts",
+        kind: "operation",
+        collects: false,
+        description: "Number of rows of every value of a column",
+        properties: &[
+            required("col", "column whose values are counted"),
+            optional("proportion", "add the proportion of the rows of every value"),
+            optional("sort", "count (default, most frequent first) or value"),
+            optional("max_categories", "number of values kept, the rest are collapsed"),
+            optional("other", "name of the collapsed values, Other by default"),
+        ],
+        example: r#"[[steps]]
+    operation = "value_counts"
+    [steps.properties]
+    col = "Class"
+    proportion = true"#,
+    },
+    OperationInfo {
+        name: "crosstab",
+        kind: "operation",
+        collects: true,
+        description: "Two-way table with the number of rows of every combination of values of two columns. Collects the counts",
+        properties: &[
+            required("rows", "column whose values are the rows of the table"),
+            required("columns", "column whose values are the columns of the table"),
+            optional("proportion", "all, row or column, to divide the counts by their total"),
+            optional("sort", "count (default, most frequent first) or value"),
+            optional("max_categories", "number of values kept in each column, the rest are collapsed"),
+            optional("other", "name of the collapsed values, Other by default"),
+        ],
+        example: r#"[[steps]]
+    operation = "crosstab"
+    [steps.properties]
+    rows = "Class"
+    columns = "Survived"
+    proportion = "row""#,
+    },
+    // Output
+    OperationInfo {
+        name: "save_csv",
+        kind: "output",
+        collects: true,
+        description: "Save the frame in a csv file. Collects the data",
+        properties: &[
+            required("path", "path to the csv file"),
+            required("delimiter", "single character separating the fields"),
+            required("header", "write a header?"),
+            optional("compression", "none, gzip, zstd or bzip2, by default given by the extension of the path, e.g. .csv.gz"),
+            optional("level", "level of the compression, 0-9 for gzip, 1-22 for zstd and 1-9 for bzip2"),
+        ],
+        example: r#"[[steps]]
+    operation = "save_csv"
+    [steps.properties]
+    path = "./iris_processed.csv"
+    delimiter = ","
+    header = true"#,
+    },
+    OperationInfo {
+        name: "split",
+        kind: "output",
+        collects: true,
+        description: "Randomly split the rows in partitions saved in csv files. Collects the data",
+        properties: &[
+            required(
+                "path",
+                "path to the csv files, {partition} is replaced by the name of each partition. Compressed if ending with .gz, .zst or .bz2",
+            ),
+            required(
+                "proportions",
+                "table with the proportion of rows of every partition, summing to 1",
+            ),
+            optional("group", "column whose rows with the same value stay together"),
+            optional("seed", "seed of the random generator, for reproducible splits"),
+            optional("delimiter", "single character separating the fields, , by default"),
+            optional("header", "write a header? true by default"),
+        ],
+        example: r#"[[steps]]
+    operation = "split"
+    [steps.properties]
+    path = "./data_{partition}.csv"
+    proportions = { train = 0.7, validation = 0.15, test = 0.15 }
+    group = "CarID"
+    seed = 7"#,
+    },
+    OperationInfo {
+        name: "explain",
+        kind: "output",
+        collects: false,
+        description: "Print the logical plans of the frame, marking where it was collected",
+        properties: &[optional(
+            "dot",
+            "path of a Graphviz DOT file to write the optimized plan",
+        )],
+        example: r#"[[steps]]
+    operation = "explain"
+    [steps.properties]
+    dot = "./plan.dot""#,
+    },
+    OperationInfo {
+        name: "preview",
+        kind: "output",
+        collects: true,
+        description: "Print some rows of the frame in the terminal",
+        properties: &[
+            optional("rows", "number of rows printed, 1000 by default"),
+            optional("mode", "head (default), tail or random rows"),
+            optional("seed", "seed of the random rows"),
+            optional("columns", "column or list of columns printed"),
+            optional("width", "maximum number of characters printed in a cell"),
+            optional("schema", "print the columns and their types"),
+            optional("count", "print the total number of rows. Computes all the steps"),
+            optional("title", "title printed above the rows"),
+        ],
+        example: r#"[[steps]]
+    operation = "preview"
+    [steps.properties]
+    title = "Cleaned data"
+    rows = 5
+    schema = true"#,
+    },
+    // Pipeline
+    OperationInfo {
+        name: "include",
+        kind: "pipeline",
+        collects: false,
+        description: "Splice the steps of another TOML file",
+        properties: &[
+            required(
+                "path",
+                "path to the included file, relative to the including file",
+            ),
+            optional("params", "values of the parameters of the included file"),
+        ],
+        example: r#"[[steps]]
+    operation = "include"
+    [steps.properties]
+    path = "./common/cleaning.toml"
+    params = { prefix = "Sepal_" }"#,
+    },
+];
+
+/// Find the description of an operation
+pub fn find_operation(name: &str) -> Option<&'static OperationInfo> {
+    CATALOG.iter().find(|op| op.name == name)
+}
+
+/// Print the available operations
+/// Prints the description, properties and an example of every operation, or of a single one if a name is given
+pub fn print_operations(name: Option<&str>) {
+    let operations: Vec<&OperationInfo> = match name {
+        None => CATALOG.iter().collect(),
+        Some(name) => vec![find_operation(name)
+            .unwrap_or_else(|| panic!("Error! {} is not a valid operation", name))],
+    };
+    for op in operations {
+        println!("{} ({}): {}", op.name, op.kind, op.description);
+        if !op.properties.is_empty() {
+            println!("Properties:");
+        }
+        for property in op.properties {
+            let required = if property.required {
+                "required"
+            } else {
+                "optional"
+            };
+            println!(
+                "  {:<14} {:<9} {}",
+                property.name, required, property.description
+            );
+        }
+        println!("Example:");
+        println!("{}", op.example);
+        println!();
+    }
+}
@@ -0,0 +1,229 @@
+// oxidf compressed csv files
+// Author: David Issa Mattos
+// Mantainer: David Issa Mattos
+
+use bzip2::read::MultiBzDecoder;
+use bzip2::write::BzEncoder;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use log::warn;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Mutex, Once};
+use std::thread;
+
+pub const COMPRESSIONS: &[&str] = &["none", "gzip", "zstd", "bzip2"];
+
+// files decompressed during the run, removed when the run finishes
+static DECOMPRESSED: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+static SIGNALS: Once = Once::new();
+
+/// Compression of a csv file
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+impl Compression {
+    /// Compression given by its name, one of COMPRESSIONS
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "none" => Compression::None,
+            "gzip" => Compression::Gzip,
+            "zstd" => Compression::Zstd,
+            "bzip2" => Compression::Bzip2,
+            _ => panic!("Error! The compression should be one of {:?}", COMPRESSIONS),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+        }
+    }
+
+    /// Compression given by the extension of a path, e.g. data.csv.gz
+    pub fn from_extension(path: &str) -> Self {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            Some("bz2") => Compression::Bzip2,
+            _ => Compression::None,
+        }
+    }
+
+    /// Compression of a file, found from its first bytes or otherwise from its extension
+    pub fn detect(path: &str) -> io::Result<Self> {
+        let mut magic = vec![];
+        File::open(path)?.take(4).read_to_end(&mut magic)?;
+        let compression = match magic.as_slice() {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            [0x28, 0xb5, 0x2f, 0xfd] => Compression::Zstd,
+            [b'B', b'Z', b'h', ..] => Compression::Bzip2,
+            _ => Compression::from_extension(path),
+        };
+        Ok(compression)
+    }
+
+    /// Levels accepted by the compression, from the fastest to the smallest files
+    pub fn levels(&self) -> RangeInclusive<u32> {
+        match self {
+            Compression::None => 0..=0,
+            Compression::Gzip => 0..=9,
+            Compression::Zstd => 1..=22,
+            Compression::Bzip2 => 1..=9,
+        }
+    }
+}
+
+/// Decompress a csv file
+/// The csv scan reads a file, so the file is decompressed in a temporary file before it is scanned.
+/// The temporary file has a random name and can only be read by the user, and it is removed when the run finishes
+/// (see DecompressedGuard). Files that are not compressed are read directly
+/// Arguments:
+/// * the path of the file
+///
+/// Return:
+/// The path of the decompressed file
+pub fn decompress(path: &str) -> io::Result<String> {
+    let compression = Compression::detect(path)?;
+    let file = File::open(path)?;
+    let mut reader: Box<dyn Read> = match compression {
+        Compression::None => return Ok(path.to_string()),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(file)),
+        Compression::Zstd => Box::new(zstd::Decoder::new(file)?),
+        Compression::Bzip2 => Box::new(MultiBzDecoder::new(file)),
+    };
+    let name = Path::new(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("data.csv");
+    let (decompressed, mut file) = create_temporary(name)?;
+    io::copy(&mut reader, &mut file)?;
+    Ok(decompressed.to_string_lossy().to_string())
+}
+
+/// Create a new file in the temporary folder, with a random name ending in the given name
+/// The file is only created if it does not exist (so a link in its place is not followed),
+/// and on unix it can only be read and written by the user
+fn create_temporary(name: &str) -> io::Result<(PathBuf, File)> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    loop {
+        let path = env::temp_dir().join(format!("oxidf-{:016x}-{}", rand::random::<u64>(), name));
+        match options.open(&path) {
+            Ok(file) => {
+                DECOMPRESSED.lock().unwrap().push(path.clone());
+                return Ok((path, file));
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Remove the files decompressed during the run
+pub fn remove_decompressed() {
+    for path in DECOMPRESSED.lock().unwrap().drain(..) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Removes the files decompressed during the run when it is dropped, at the end of the run or when it fails.
+/// When the run is interrupted (SIGINT or SIGTERM) the files are removed before exiting
+pub struct DecompressedGuard;
+impl DecompressedGuard {
+    pub fn start() -> Self {
+        SIGNALS.call_once(|| match Signals::new([SIGINT, SIGTERM]) {
+            Ok(mut signals) => {
+                thread::spawn(move || {
+                    if let Some(signal) = signals.forever().next() {
+                        remove_decompressed();
+                        process::exit(128 + signal);
+                    }
+                });
+            }
+            Err(e) => warn!(
+                "Cannot remove the decompressed files when interrupted: {}",
+                e
+            ),
+        });
+        DecompressedGuard
+    }
+}
+impl Drop for DecompressedGuard {
+    fn drop(&mut self) {
+        remove_decompressed();
+    }
+}
+
+/// Writer compressing what is written to it
+/// finish should be called after the last write, to complete the compressed file
+pub enum CompressedWriter<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+    Bzip2(BzEncoder<W>),
+}
+impl<W: Write> CompressedWriter<W> {
+    /// Compress with the given level, or the default level of the compression
+    pub fn new(writer: W, compression: Compression, level: Option<u32>) -> io::Result<Self> {
+        let compressed = match compression {
+            Compression::None => CompressedWriter::Plain(writer),
+            Compression::Gzip => CompressedWriter::Gzip(GzEncoder::new(
+                writer,
+                level.map_or(flate2::Compression::default(), flate2::Compression::new),
+            )),
+            Compression::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(
+                writer,
+                level.map_or(zstd::DEFAULT_COMPRESSION_LEVEL, |l| l as i32),
+            )?),
+            Compression::Bzip2 => CompressedWriter::Bzip2(BzEncoder::new(
+                writer,
+                level.map_or(bzip2::Compression::default(), bzip2::Compression::new),
+            )),
+        };
+        Ok(compressed)
+    }
+    pub fn finish(self) -> io::Result<W> {
+        let mut writer = match self {
+            CompressedWriter::Plain(writer) => writer,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+            CompressedWriter::Bzip2(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+            CompressedWriter::Bzip2(encoder) => encoder.write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+            CompressedWriter::Bzip2(encoder) => encoder.flush(),
+        }
+    }
+}
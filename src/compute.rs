@@ -2,112 +2,353 @@
 // Author: David Issa Mattos
 // Mantainer: David Issa Mattos
 
+use crate::catalog::find_operation;
+use crate::compression::DecompressedGuard;
+use crate::graph::*;
 use crate::input::*;
+use crate::logging::set_step;
 use crate::operations::*;
 use crate::output::*;
+use crate::profile::*;
 use crate::steps::*;
-use crate::merge::*;
+use crate::summary::*;
+use log::{debug, info, trace, warn};
 use polars::prelude::*;
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+/// Options of a pipeline run
+pub struct RunOptions {
+    // print the logical plans of the frame read by every output step before running it
+    pub explain: bool,
+    // record the time, rows, columns and memory of every step
+    pub profile: bool,
+    // collect the frame at the end of every step to count its rows when profiling
+    pub profile_collect: bool,
+    // path of a JSON file where the profile is saved
+    pub profile_json: Option<String>,
+}
 
 ///Execute a pipeline
+/// The steps are executed following the dependency graph of the pipeline.
+/// Every step runs as soon as the frame it reads is ready, so independent branches run concurrently.
+/// When more than one step reads the same frame, the frame is collected once and shared between them
 /// Arguments:
 /// * a reference to a pipeline struct
-/// * a boolean indicating if the messages will be shown
-pub fn compute_pipeline(pipeline: &Pipeline, messages: bool) {
-    println!(
+/// * the options of the run
+pub fn compute_pipeline(pipeline: &Pipeline, options: &RunOptions) {
+    info!(
         "Running pipeline: {}, version {}",
         pipeline.general.name, pipeline.general.version
     );
-    //TODO: fix fake initialization
-    let mut df: LazyFrame = df![
-    "A"   => [1, 2],
-    "B"   => ["AA", "BB"],
-    ]
-    .unwrap()
-    .lazy();
-    //Loop over all pipeline steps
-    for (i, step) in pipeline.steps.iter().enumerate() {
-        if messages {
-            println!("Step {}: {}", i, step.operation);
-        }
-        if i == 0 {}
-        let operation = step.operation.as_str();
-        match operation {
-            //Input
-            "read_csv" => {
-                let s = ReadCsvStep::from(step);
-                df = s.execute().unwrap();
-            }
-            //Operations
-            "rename" => {
-                let s: RenameStep = RenameStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "select" => {
-                let s: SelectColumnsStep = SelectColumnsStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "filter_eq" => {
-                let s: FilterEqualStep = FilterEqualStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "filter_gt" => {
-                let s: FilterGreaterStep = FilterGreaterStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "filter_gt_eq" => {
-                let s: FilterGreaterEqStep = FilterGreaterEqStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "filter_lt" => {
-                let s: FilterSmallerStep = FilterSmallerStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "filter_lt_eq" => {
-                let s: FilterSmallerEqStep = FilterSmallerEqStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "filter_isin" => {
-                let s: FilterIsInStep = FilterIsInStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "filter_contains" => {
-                let s: FilterContainsStep = FilterContainsStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "remove_na" => {
-                let s: RemoveNAStep = RemoveNAStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "recode" => {
-                let s: RecodeStep = RecodeStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "cast" => {
-                let s: CastStep = CastStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "anonymize" => {
-                let s: AnonymizeStep = AnonymizeStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "pivot" => {
-                let s: PivotStep = PivotStep::from(step, df);
-                df = s.execute().unwrap();
-            }
-            "unique" => {
-                let s: UniqueStep = UniqueStep::from(step, df);
-                df = s.execute().unwrap();
+    debug!(
+        "{} (mantainer: {})",
+        pipeline.general.description, pipeline.general.mantainer
+    );
+    let graph = PipelineGraph::from(pipeline).unwrap_or_else(|e| panic!("Error! {}", e));
+    let n_steps = pipeline.steps.len();
+    let start = Instant::now();
+    let mut profiles: Vec<StepProfile> = vec![];
+    let decompressed = DecompressedGuard::start();
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel::<(usize, StepResult, Option<StepProfile>)>();
+        let spawn_step = |i: usize, df: Option<LazyFrame>, rows_in: Option<usize>| {
+            let sender = sender.clone();
+            let step = &pipeline.steps[i];
+            let graph = &graph;
+            let readers = graph.children[i].len();
+            let shared = readers > 1;
+            scope.spawn(move || {
+                set_step(Some((i, step.operation.as_str())));
+                debug!("Running step");
+                // the plans go to stderr, so they do not mix with a csv written to stdout
+                if options.explain && OUTPUT_OP.contains(&step.operation.as_str()) {
+                    if let Some(plans) = df.as_ref().and_then(|df| describe_plans(df).ok()) {
+                        let collected = describe_materialization(pipeline, graph, i);
+                        eprintln!("Step {}: {}\n{}\n{}", i, step.operation, collected, plans);
+                    }
+                }
+                let step_start = Instant::now();
+                let columns_in = df.as_ref().and_then(count_columns);
+                let result = catch_unwind(AssertUnwindSafe(|| {
+                    let df = execute_step(step, df)?;
+                    // collect once instead of recomputing the same plan in every step reading this frame
+                    if shared {
+                        debug!("Collecting the frame, read by {} steps", readers);
+                    }
+                    match shared || options.profile_collect {
+                        true => collect_frame(df),
+                        false => Ok((df, None)),
+                    }
+                }))
+                .unwrap_or_else(|_| Err(String::from("the step panicked")))
+                .map_err(|e| format!("Step {} ({}) failed: {}", i, step.operation, e));
+                let profile = match (&result, options.profile) {
+                    (Ok((df, rows_out)), true) => Some(StepProfile {
+                        step: i,
+                        operation: step.operation.clone(),
+                        wall_time_ms: step_start.elapsed().as_secs_f64() * 1000.0,
+                        rows_in,
+                        rows_out: *rows_out,
+                        columns_in,
+                        columns_out: count_columns(df),
+                        peak_memory_kb: peak_memory_kb(),
+                    }),
+                    _ => None,
+                };
+                trace!(
+                    "Finished in {:.1} ms",
+                    step_start.elapsed().as_secs_f64() * 1000.0
+                );
+                set_step(None);
+                sender.send((i, result, profile)).unwrap();
+            });
+        };
+
+        for (i, parent) in graph.parents.iter().enumerate() {
+            if parent.is_none() {
+                spawn_step(i, None, None);
             }
-            //Output
-            "preview" => {
-                let s: PreviewStep = PreviewStep::from(step, df);
-                df = s.execute().unwrap();
+        }
+        for _ in 0..n_steps {
+            let (i, result, profile) = receiver.recv().unwrap();
+            let (df, rows) = result.unwrap_or_else(|e| panic!("Error computing the steps. {}", e));
+            profiles.extend(profile);
+            for child in &graph.children[i] {
+                spawn_step(*child, Some(df.clone()), rows);
             }
+        }
+    });
+    drop(decompressed);
+    log_run_summary();
+
+    if options.profile {
+        profiles.sort_by_key(|p| p.step);
+        let profile = PipelineProfile {
+            pipeline: pipeline.general.name.clone(),
+            version: pipeline.general.version.clone(),
+            wall_time_ms: start.elapsed().as_secs_f64() * 1000.0,
+            peak_memory_kb: peak_memory_kb(),
+            steps: profiles,
+        };
+        profile.print();
+        if let Some(path) = &options.profile_json {
+            profile
+                .save_json(path)
+                .unwrap_or_else(|e| panic!("Error writing the profile to {}: {}", path, e));
+        }
+    }
+}
+
+/// Log the rows accepted and rejected from the csv files read with a quarantine or max_rejected
+/// Files with rejected rows are logged as warnings
+fn log_run_summary() {
+    for r in take_rejections() {
+        let message = format!(
+            "Run summary: {}: {} rows accepted, {} rejected{}",
+            r.path,
+            r.accepted,
+            r.rejected,
+            r.quarantine
+                .map_or(String::new(), |q| format!(" (written to {})", q))
+        );
+        match r.rejected {
+            0 => info!("{}", message),
+            _ => warn!("{}", message),
+        }
+    }
+}
+
+// frame produced by a step, with its number of rows if it was collected
+type StepResult = Result<(LazyFrame, Option<usize>), String>;
+
+fn collect_frame(df: LazyFrame) -> StepResult {
+    let collected = df.collect().map_err(|e| e.to_string())?;
+    let rows = collected.height();
+    Ok((collected.lazy(), Some(rows)))
+}
+
+fn count_columns(df: &LazyFrame) -> Option<usize> {
+    df.schema().ok().map(|schema| schema.len())
+}
 
-            _ => {
-                panic!("Error computing the steps. Step {} failed", operation);
+/// Explain a pipeline
+/// Prints the logical plans of the frame read by every output step, without running the outputs,
+/// together with the steps where this frame is collected when the pipeline runs.
+/// Steps that read the data or write files while their plan is built (see runs_eagerly) are not executed,
+/// so the plans of the outputs after them are not shown
+/// Arguments:
+/// * a reference to a pipeline struct
+/// * an optional folder where the optimized plan of every output is written as a Graphviz DOT file
+pub fn explain_pipeline(pipeline: &Pipeline, dot: Option<&str>) {
+    let graph = PipelineGraph::from(pipeline).unwrap_or_else(|e| panic!("Error! {}", e));
+    let _decompressed = DecompressedGuard::start();
+    let mut frames: Vec<Option<LazyFrame>> = vec![None; pipeline.steps.len()];
+    // step before every step that is not executed, so its plan is unknown
+    let mut eager: Vec<Option<String>> = vec![None; pipeline.steps.len()];
+    for i in graph.order() {
+        let step = &pipeline.steps[i];
+        let df = graph.parents[i].and_then(|p| frames[p].clone());
+        eager[i] = graph.parents[i].and_then(|p| eager[p].clone());
+        let is_output = OUTPUT_OP.contains(&step.operation.as_str());
+        if eager[i].is_none() && !is_output && runs_eagerly(step) {
+            eager[i] = Some(format!("step {} ({})", i, step.operation));
+        }
+        if !is_output {
+            if eager[i].is_none() {
+                let df = execute_step(step, df).unwrap_or_else(|e| {
+                    panic!("Error! Step {} ({}) failed: {}", i, step.operation, e)
+                });
+                frames[i] = Some(df);
             }
+            continue;
+        }
+        println!("Step {}: {}", i, step.operation);
+        if let Some(point) = &eager[i] {
+            println!(
+                "The plan is only known when the pipeline runs, since {} reads the data or writes files\n",
+                point
+            );
+            continue;
+        }
+        let df = df.unwrap();
+        println!("{}", describe_materialization(pipeline, &graph, i));
+        let plans = describe_plans(&df)
+            .unwrap_or_else(|e| panic!("Error optimizing the plan of step {}: {}", i, e));
+        println!("{}", plans);
+        if let Some(folder) = dot {
+            let path = Path::new(folder).join(format!("step_{}_{}.dot", i, step.operation));
+            let content = df
+                .to_dot(true)
+                .unwrap_or_else(|e| panic!("Error creating the plan of step {}: {}", i, e));
+            fs::write(&path, content)
+                .unwrap_or_else(|e| panic!("Error writing {}: {}", path.display(), e));
+            println!("Optimized plan written to {}", path.display());
         }
+        frames[i] = Some(df);
     }
 }
+
+/// Does the step collect the frame it passes on when the pipeline runs?
+/// These are the operations of the catalog that collect the data, plus read_csv checking its rows
+/// and resample filling the upsampled rows
+fn collects_frame(step: &Steps) -> bool {
+    let operation = step.operation.as_str();
+    let has = |name: &str| step.properties.contains_key(name);
+    find_operation(operation).is_some_and(|op| op.collects)
+        || match operation {
+            "read_csv" => has("quarantine") || has("max_rejected"),
+            "resample" => step
+                .properties
+                .get("fill")
+                .is_some_and(|f| f.as_str() != Some("none")),
+            _ => false,
+        }
+}
+
+/// Does the step read the data or write files when its plan is built?
+/// These are the steps collecting their frame, and the ones computing their plan from the data
+/// (e.g. the breaks of bin) or writing files (e.g. the mapping of encode)
+fn runs_eagerly(step: &Steps) -> bool {
+    let has = |name: &str| step.properties.contains_key(name);
+    collects_frame(step)
+        || match step.operation.as_str() {
+            "bin" => !has("breaks"),
+            "encode" => (!has("load_mapping") && !has("order")) || has("mapping"),
+            _ => false,
+        }
+}
+
+/// Steps before a step where the frame is collected when the pipeline runs
+/// These are the operations that collect the data and the frames shared by more than one step
+fn materialization_points(pipeline: &Pipeline, graph: &PipelineGraph, step: usize) -> Vec<String> {
+    let mut points = vec![];
+    let mut current = graph.parents[step];
+    while let Some(i) = current {
+        let operation = pipeline.steps[i].operation.as_str();
+        let is_output = OUTPUT_OP.contains(&operation);
+        if !is_output && collects_frame(&pipeline.steps[i]) {
+            points.push(format!("step {} ({})", i, operation));
+        } else if graph.children[i].len() > 1 {
+            points.push(format!(
+                "step {} ({}, shared by {} steps)",
+                i,
+                operation,
+                graph.children[i].len()
+            ));
+        }
+        current = graph.parents[i];
+    }
+    points.reverse();
+    points
+}
+
+/// Line of the explanation of a step listing the steps where its frame is collected
+fn describe_materialization(pipeline: &Pipeline, graph: &PipelineGraph, step: usize) -> String {
+    let materialized = materialization_points(pipeline, graph, step);
+    if materialized.is_empty() {
+        String::from("The frame is not collected before this step")
+    } else {
+        format!("The frame is collected at: {}", materialized.join(", "))
+    }
+}
+
+/// Execute a single step
+/// Arguments:
+/// * the step to execute
+/// * the frame the step reads (None for input steps)
+fn execute_step(step: &Steps, df: Option<LazyFrame>) -> Result<LazyFrame, String> {
+    let operation = step.operation.as_str();
+    let result = match (operation, df) {
+        //Input
+        ("read_csv", _) => ReadCsvStep::from(step).execute(),
+        //Operations
+        ("rename", Some(df)) => RenameStep::from(step, df).execute(),
+        ("select", Some(df)) => SelectColumnsStep::from(step, df).execute(),
+        ("filter_eq", Some(df)) => FilterEqualStep::from(step, df).execute(),
+        ("filter_gt", Some(df)) => FilterGreaterStep::from(step, df).execute(),
+        ("filter_gt_eq", Some(df)) => FilterGreaterEqStep::from(step, df).execute(),
+        ("filter_lt", Some(df)) => FilterSmallerStep::from(step, df).execute(),
+        ("filter_lt_eq", Some(df)) => FilterSmallerEqStep::from(step, df).execute(),
+        ("filter_isin", Some(df)) => FilterIsInStep::from(step, df).execute(),
+        ("filter_contains", Some(df)) => FilterContainsStep::from(step, df).execute(),
+        ("remove_na", Some(df)) => RemoveNAStep::from(step, df).execute(),
+        ("recode", Some(df)) => RecodeStep::from(step, df).execute(),
+        ("cast", Some(df)) => CastStep::from(step, df).execute(),
+        ("anonymize", Some(df)) => AnonymizeStep::from(step, df).execute(),
+        ("mask_keep_last", Some(df)) => MaskKeepLastStep::from(step, df).execute(),
+        ("mask_email", Some(df)) => MaskEmailStep::from(step, df).execute(),
+        ("mask_regex", Some(df)) => MaskRegexStep::from(step, df).execute(),
+        ("mask_range", Some(df)) => MaskRangeStep::from(step, df).execute(),
+        ("mask_date", Some(df)) => MaskDateStep::from(step, df).execute(),
+        ("pivot", Some(df)) => PivotStep::from(step, df).execute(),
+        ("unique", Some(df)) => UniqueStep::from(step, df).execute(),
+        ("sample", Some(df)) => SampleStep::from(step, df).execute(),
+        ("head", Some(df)) => HeadStep::from(step, df).execute(),
+        ("tail", Some(df)) => TailStep::from(step, df).execute(),
+        ("slice", Some(df)) => SliceStep::from(step, df).execute(),
+        ("window", Some(df)) => WindowStep::from(step, df).execute(),
+        ("rolling", Some(df)) => RollingStep::from(step, df).execute(),
+        ("resample", Some(df)) => ResampleStep::from(step, df).execute(),
+        ("bin", Some(df)) => BinStep::from(step, df).execute(),
+        ("encode", Some(df)) => EncodeStep::from(step, df).execute(),
+        //Summary
+        ("describe", Some(df)) => DescribeStep::from(step, df).execute(),
+        ("value_counts", Some(df)) => ValueCountsStep::from(step, df).execute(),
+        ("crosstab", Some(df)) => CrosstabStep::from(step, df).execute(),
+        //Output
+        ("save_csv", Some(df)) => SaveCsvStep::from(step, df).execute(),
+        ("split", Some(df)) => SplitStep::from(step, df).execute(),
+        ("preview", Some(df)) => PreviewStep::from(step, df).execute(),
+        ("explain", Some(df)) => ExplainStep::from(step, df).execute(),
+
+        (_, None) => return Err(String::from("there is no frame to read")),
+        _ => return Err(String::from("unknown operation")),
+    };
+    result.map_err(|e| e.to_string())
+}
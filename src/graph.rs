@@ -0,0 +1,112 @@
+// oxidf dependency graph of the pipeline steps
+// Author: David Issa Mattos
+// Mantainer: David Issa Mattos
+
+use crate::input::INPUT_OP;
+use crate::steps::*;
+use std::collections::HashMap;
+
+/// Pipeline graph
+/// Each step is a node and depends on the frame it reads.
+/// A step reads the frame named in its `input` field or, if there is none, the frame of the previous step.
+/// Input steps (e.g. read_csv) do not read any frame and are the roots of the graph
+pub struct PipelineGraph {
+    // index of the step producing the frame read by each step
+    pub parents: Vec<Option<usize>>,
+    // indexes of the steps reading the frame of each step
+    pub children: Vec<Vec<usize>>,
+}
+impl PipelineGraph {
+    /// Build the graph from the pipeline
+    /// Fails if a step reads a frame that does not exist, if two steps share a name or if the steps depend on each other in a cycle
+    pub fn from(pipeline: &Pipeline) -> Result<Self, String> {
+        let steps = &pipeline.steps;
+        let mut names: HashMap<&str, usize> = HashMap::new();
+        for (i, step) in steps.iter().enumerate() {
+            if let Some(name) = &step.name {
+                if let Some(j) = names.insert(name.as_str(), i) {
+                    return Err(format!(
+                        "Steps {} and {} are both named '{}'. Frame names should be unique",
+                        j, i, name
+                    ));
+                }
+            }
+        }
+
+        let mut parents: Vec<Option<usize>> = vec![];
+        for (i, step) in steps.iter().enumerate() {
+            let is_input = INPUT_OP.contains(&step.operation.as_str());
+            let parent = match (&step.input, is_input) {
+                (Some(input), true) => {
+                    return Err(format!(
+                        "Step {} ({}) is an input step and cannot read the frame '{}'",
+                        i, step.operation, input
+                    ))
+                }
+                (None, true) => None,
+                (Some(input), false) => match names.get(input.as_str()) {
+                    Some(j) if *j == i => {
+                        return Err(format!(
+                            "Step {} ({}) reads its own frame",
+                            i, step.operation
+                        ))
+                    }
+                    Some(j) => Some(*j),
+                    None => {
+                        return Err(format!(
+                            "Step {} ({}) reads the frame '{}' but no step has this name",
+                            i, step.operation, input
+                        ))
+                    }
+                },
+                (None, false) if i == 0 => {
+                    return Err(format!(
+                        "Step 0 ({}) has no frame to read. The first step should be an input step",
+                        step.operation
+                    ))
+                }
+                (None, false) => Some(i - 1),
+            };
+            parents.push(parent);
+        }
+
+        let mut children: Vec<Vec<usize>> = vec![vec![]; steps.len()];
+        for (i, parent) in parents.iter().enumerate() {
+            if let Some(p) = parent {
+                children[*p].push(i);
+            }
+        }
+        let graph = PipelineGraph { parents, children };
+        graph.check_cycles(pipeline)?;
+        Ok(graph)
+    }
+
+    /// Topological order of the steps
+    /// Every step comes after the step producing the frame it reads. Steps that are part of a cycle are left out
+    pub fn order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.parents.len())
+            .filter(|i| self.parents[*i].is_none())
+            .collect();
+        let mut pos = 0;
+        while pos < order.len() {
+            order.extend(self.children[order[pos]].iter().copied());
+            pos += 1;
+        }
+        order
+    }
+
+    fn check_cycles(&self, pipeline: &Pipeline) -> Result<(), String> {
+        let order = self.order();
+        if order.len() == self.parents.len() {
+            return Ok(());
+        }
+        let cycle: Vec<String> = (0..self.parents.len())
+            .filter(|i| !order.contains(i))
+            .map(|i| format!("{} ({})", i, pipeline.steps[i].operation))
+            .collect();
+        Err(format!(
+            "The steps {} are part of (or read from) a cycle of frames",
+            cycle.join(", ")
+        ))
+    }
+}
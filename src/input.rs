@@ -2,32 +2,110 @@
 // Author: David Issa Mattos
 // Mantainer: David Issa Mattos
 
-use crate::steps::{self, *};
+use crate::compression::*;
+use crate::steps::*;
+use crate::utils::*;
+use log::warn;
 use polars::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::sync::Mutex;
 
 pub const INPUT_OP: &[&str] = &["read_csv"];
 
+// rows accepted and rejected from the files read with a quarantine or max_rejected, for the run summary
+static REJECTIONS: Mutex<Vec<Rejection>> = Mutex::new(vec![]);
+
+/// Rows accepted and rejected from a csv file
+pub struct Rejection {
+    pub path: String,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub quarantine: Option<String>,
+}
+
+/// Rows accepted and rejected from every file read since the last call
+pub fn take_rejections() -> Vec<Rejection> {
+    REJECTIONS.lock().unwrap().drain(..).collect()
+}
+
 /// Read CSV
 /// Read a csv file given a delimiter and optional header. Polars will read this as a lazy DataFrame to pass on to the rest of the pipeline
+/// Files compressed with gzip, zstd or bzip2 are decompressed in a temporary file before they are read
 /// Arguments:
 /// * a literal string path
 /// * a delimiter: commonly b',' or b';' or b' ' or b'\t'
 /// * a boolean indicating if there is a header or not
+/// * optionally, the options of the reader (see CsvOptions)
+///
 /// Return:
 /// A LazyFrame encapsulated in Result
 pub struct ReadCsvStep {
     delimiter: u8,
     header: bool,
     path: String,
+    options: CsvOptions,
+}
+/// How a csv file is read
+pub struct CsvOptions {
+    // lines skipped before the header
+    pub skip_rows: usize,
+    pub comment: Option<u8>,
+    pub quote: Option<u8>,
+    // strings read as null values
+    pub null_values: Option<Vec<String>>,
+    // replace the invalid utf8 characters instead of failing
+    pub lossy: bool,
+    // names of the types of some columns, instead of inferring them
+    pub dtypes: Vec<(String, String)>,
+    // rows used to infer the types of the columns, all the rows if None
+    pub infer_schema_length: Option<usize>,
+    pub columns: Option<Vec<String>>,
+    pub n_rows: Option<usize>,
+    pub parse_dates: bool,
+    // values that cannot be parsed are null, instead of failing
+    pub ignore_errors: bool,
+    // numbers written with a decimal comma (and optionally dots separating the thousands), e.g. 1.234,5
+    pub decimal_comma: bool,
+    // csv file where the rows that cannot be read are written, with their line and the reason
+    pub quarantine: Option<String>,
+    // fraction of rejected rows above which the file is not read
+    pub max_rejected: Option<f64>,
+}
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            skip_rows: 0,
+            comment: None,
+            quote: Some(b'"'),
+            null_values: None,
+            lossy: false,
+            dtypes: vec![],
+            infer_schema_length: Some(100),
+            columns: None,
+            n_rows: None,
+            parse_dates: false,
+            ignore_errors: true,
+            decimal_comma: false,
+            quarantine: None,
+            max_rejected: None,
+        }
+    }
 }
 impl ReadCsvStep {
     pub fn new(path: &str, delimiter: u8, header: bool) -> Self {
         ReadCsvStep {
-            delimiter: delimiter,
-            header: header,
+            delimiter,
+            header,
             path: String::from(path),
+            options: CsvOptions::default(),
         }
     }
+    pub fn with_options(mut self, options: CsvOptions) -> Self {
+        self.options = options;
+        self
+    }
     pub fn from(step: &Steps) -> Self {
         let path = step
             .properties
@@ -44,30 +122,496 @@ impl ReadCsvStep {
             .unwrap()
             .to_string()
             .as_bytes()
-            .get(0)
-            .unwrap()
-            .clone();
+            .first()
+            .copied()
+            .unwrap();
         let header = step.properties.get("header").unwrap().as_bool().unwrap();
-        ReadCsvStep {
-            delimiter: delimiter,
-            header: header,
-            path: path,
+        let properties = &step.properties;
+        let defaults = CsvOptions::default();
+        let get_usize = |name: &str| {
+            properties
+                .get(name)
+                .map(|v| v.as_integer().unwrap() as usize)
+        };
+        let get_bool = |name: &str, default: bool| {
+            properties
+                .get(name)
+                .map_or(default, |v| v.as_bool().unwrap())
+        };
+        let get_char = |name: &str| {
+            properties
+                .get(name)
+                .map(|v| v.as_str().unwrap().as_bytes().first().copied())
+        };
+        let options = CsvOptions {
+            skip_rows: get_usize("skip_rows").unwrap_or(defaults.skip_rows),
+            comment: get_char("comment").unwrap_or(defaults.comment),
+            quote: get_char("quote").unwrap_or(defaults.quote),
+            null_values: properties.get("null_values").map(get_columns),
+            lossy: properties
+                .get("encoding")
+                .is_some_and(|e| e.as_str() == Some("lossy")),
+            dtypes: properties.get("dtypes").map_or(vec![], |d| {
+                d.as_table()
+                    .unwrap()
+                    .iter()
+                    .map(|(c, t)| (c.clone(), t.as_str().unwrap().to_string()))
+                    .collect()
+            }),
+            infer_schema_length: match properties.get("infer_schema_length") {
+                Some(length) => Some(length.as_integer().unwrap() as usize).filter(|l| *l > 0),
+                None => defaults.infer_schema_length,
+            },
+            columns: properties.get("columns").map(get_columns),
+            n_rows: get_usize("n_rows"),
+            parse_dates: get_bool("parse_dates", defaults.parse_dates),
+            ignore_errors: get_bool("ignore_errors", defaults.ignore_errors),
+            decimal_comma: get_bool("decimal_comma", defaults.decimal_comma),
+            quarantine: properties
+                .get("quarantine")
+                .map(|p| p.as_str().unwrap().to_string()),
+            max_rejected: properties.get("max_rejected").map(get_number),
+        };
+        ReadCsvStep::new(&path, delimiter, header).with_options(options)
+    }
+
+    /// Types given to some columns
+    /// With a decimal comma the float columns are read as strings, and converted after reading
+    fn dtypes(&self) -> Schema {
+        let options = &self.options;
+        options
+            .dtypes
+            .iter()
+            .map(|(c, t)| {
+                let dtype = match get_dtype(t, "us") {
+                    DataType::Float32 | DataType::Float64 if options.decimal_comma => {
+                        DataType::Utf8
+                    }
+                    dtype => dtype,
+                };
+                Field::new(c, dtype)
+            })
+            .collect()
+    }
+
+    /// String columns written with a decimal comma, found in the first rows of the file
+    fn decimal_comma_columns(
+        &self,
+        path: &str,
+        dtypes: &Schema,
+    ) -> Result<Vec<String>, PolarsError> {
+        let options = &self.options;
+        let sample = CsvReader::from_path(path)?
+            .with_delimiter(self.delimiter)
+            .has_header(self.header)
+            .with_skip_rows(options.skip_rows)
+            .with_comment_char(options.comment)
+            .with_quote_char(options.quote)
+            .with_null_values(options.null_values.clone().map(NullValues::AllColumns))
+            .with_encoding(CsvEncoding::LossyUtf8)
+            .with_n_rows(Some(options.infer_schema_length.unwrap_or(100)))
+            .with_dtypes(Some(dtypes))
+            .with_ignore_parser_errors(true)
+            .finish()?;
+        let declared = |name: &str| options.dtypes.iter().any(|(c, _)| c == name);
+        let columns = sample
+            .get_columns()
+            .iter()
+            .filter(|s| {
+                let values = match s.utf8() {
+                    Ok(values) if !declared(s.name()) => values,
+                    _ => return false,
+                };
+                values.into_iter().flatten().any(|v| v.contains(','))
+                    && values.into_iter().flatten().all(is_decimal_comma)
+            })
+            .map(|s| s.name().to_string())
+            .collect();
+        Ok(columns)
+    }
+
+    /// Reader of the file with the options shared by every read
+    fn reader(&self, path: &str) -> LazyCsvReader<'_> {
+        let options = &self.options;
+        LazyCsvReader::new(path)
+            .with_delimiter(self.delimiter)
+            .has_header(self.header)
+            .with_skip_rows(options.skip_rows)
+            .with_comment_char(options.comment)
+            .with_quote_char(options.quote)
+            .with_null_values(options.null_values.clone().map(NullValues::AllColumns))
+            .with_encoding(match options.lossy {
+                true => CsvEncoding::LossyUtf8,
+                false => CsvEncoding::Utf8,
+            })
+            .with_n_rows(options.n_rows)
+    }
+
+    /// Go through the records of the file, with their position, the line where they start, their text and their number of fields
+    /// The file is read line by line. Skipped, empty and comment lines are left out, as well as the header
+    ///
+    /// Return:
+    /// The number of records
+    fn scan_records(
+        &self,
+        path: &str,
+        mut visit: impl FnMut(usize, usize, &str, usize),
+    ) -> Result<usize, PolarsError> {
+        let options = &self.options;
+        let skipped = usize::from(self.header);
+        let limit = options.n_rows.map_or(usize::MAX, |n| n + skipped);
+        let mut emit = |count: usize, start: usize, content: &str| {
+            if count >= skipped {
+                let fields = count_fields(content, self.delimiter, options.quote);
+                visit(count - skipped, start, content, fields);
+            }
+        };
+        let mut count = 0;
+        let mut reader = BufReader::new(fs::File::open(path)?);
+        let mut buffer = vec![];
+        let mut record: Option<(usize, String)> = None;
+        let mut i = 0;
+        while count < limit {
+            buffer.clear();
+            if reader.read_until(b'\n', &mut buffer)? == 0 {
+                break;
+            }
+            i += 1;
+            if i <= options.skip_rows {
+                continue;
+            }
+            let line = String::from_utf8_lossy(&buffer);
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let (start, content) = match record.take() {
+                Some((start, content)) => (start, format!("{}\n{}", content, line)),
+                None if line.is_empty() => continue,
+                None if options.comment.is_some_and(|c| line.as_bytes()[0] == c) => continue,
+                None => (i, line.to_string()),
+            };
+            // a quoted field goes on in the next line
+            if options
+                .quote
+                .is_some_and(|q| content.bytes().filter(|b| *b == q).count() % 2 == 1)
+            {
+                record = Some((start, content));
+                continue;
+            }
+            emit(count, start, &content);
+            count += 1;
+        }
+        if let Some((start, content)) = record {
+            if count < limit {
+                emit(count, start, &content);
+                count += 1;
+            }
         }
+        Ok(count.saturating_sub(skipped))
     }
-}
-impl Execute for ReadCsvStep {
-    fn execute(&self) -> Result<LazyFrame, PolarsError> {
-        let df = LazyCsvReader::new(self.path.clone())
+
+    /// Remove the rows that cannot be read, instead of reading them with null values
+    /// A row is rejected when it has a different number of fields than the header,
+    /// or when one of its values cannot be parsed to the type of its column.
+    /// The rejected rows are written to the quarantine file with their line and the reason,
+    /// and the file fails to be read when the fraction of rejected rows is above max_rejected.
+    /// The rows are read with the types of the columns next to their text, so the values that are null only
+    /// with the types are the ones that cannot be parsed. The numbers of fields are counted going through the file
+    fn reject_rows(&self, path: &str, df: LazyFrame) -> Result<LazyFrame, PolarsError> {
+        let options = &self.options;
+        let schema = df.schema()?;
+        let columns: Vec<(String, DataType)> = schema
+            .iter()
+            .map(|(c, dtype)| (c.to_string(), dtype.clone()))
+            .collect();
+        let width = columns.len();
+        let mut wrong_fields: HashMap<usize, String> = HashMap::new();
+        let records = self.scan_records(path, |i, _, _, fields| {
+            if fields != width {
+                wrong_fields.insert(i, format!("expected {} fields, found {}", width, fields));
+            }
+        })?;
+
+        let strings: Schema = columns
+            .iter()
+            .map(|(c, _)| Field::new(c, DataType::Utf8))
+            .collect();
+        // the strings are read by their own reader, since the lazy scans of a file share a single read
+        let raw = CsvReader::from_path(path)?
             .with_delimiter(self.delimiter)
             .has_header(self.header)
+            .with_skip_rows(options.skip_rows)
+            .with_comment_char(options.comment)
+            .with_quote_char(options.quote)
+            .with_null_values(options.null_values.clone().map(NullValues::AllColumns))
+            .with_encoding(match options.lossy {
+                true => CsvEncoding::LossyUtf8,
+                false => CsvEncoding::Utf8,
+            })
+            .with_n_rows(options.n_rows)
+            .with_dtypes(Some(&strings))
             .with_ignore_parser_errors(true)
+            .finish()?
+            .lazy()
+            .select(
+                columns
+                    .iter()
+                    .map(|(c, _)| col(c).alias(&format!("__raw_{}", c)))
+                    .collect::<Vec<Expr>>(),
+            )
+            .with_row_count("__row", None);
+        // the first value that is null only with the type of its column is the reason of the rejection
+        let mut reason = lit(Null {}).cast(DataType::Utf8);
+        for (c, dtype) in columns.iter().rev() {
+            let text = col(&format!("__raw_{}", c));
+            reason = when(col(c).is_null().and(text.clone().is_not_null()))
+                .then(concat_str(
+                    [text, lit(format!(" of {} is not a valid {}", c, dtype))],
+                    "",
+                ))
+                .otherwise(reason);
+        }
+        let mut selected: Vec<Expr> = columns.iter().map(|(c, _)| col(c)).collect();
+        selected.push(reason.alias("__reason"));
+        let typed = match options.n_rows {
+            Some(n_rows) => df.limit(n_rows as IdxSize),
+            None => df,
+        }
+        .with_row_count("__row", None)
+        .left_join(raw, col("__row"), col("__row"))
+        .select(selected)
+        .collect()?;
+
+        // the records found in the file can only be matched with the rows read when there are as many
+        let aligned = records == typed.height();
+        if !aligned {
+            warn!(
+                "{} has {} records, but {} rows are read. Only the values that cannot be parsed are rejected, without their line",
+                self.path,
+                records,
+                typed.height()
+            );
+            wrong_fields.clear();
+        }
+        let rejected: Vec<(usize, String)> = typed
+            .column("__reason")?
+            .utf8()?
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, reason)| match wrong_fields.remove(&i) {
+                Some(fields) => Some((i, fields)),
+                None => reason.map(|r| (i, r.to_string())),
+            })
+            .collect();
+        let mut lines: HashMap<usize, (usize, String)> = HashMap::new();
+        if options.quarantine.is_some() && aligned && !rejected.is_empty() {
+            let wanted: HashSet<usize> = rejected.iter().map(|(i, _)| *i).collect();
+            self.scan_records(path, |i, line, content, _| {
+                if wanted.contains(&i) {
+                    lines.insert(i, (line, content.to_string()));
+                }
+            })?;
+        }
+
+        if let Some(path) = &options.quarantine {
+            let line = |i: &usize| lines.get(i);
+            let mut quarantine = df!(
+                "line" => rejected.iter().map(|(i, _)| line(i).map(|l| l.0 as i64)).collect::<Vec<Option<i64>>>(),
+                "reason" => rejected.iter().map(|(_, r)| r.as_str()).collect::<Vec<&str>>(),
+                "content" => rejected.iter().map(|(i, _)| line(i).map(|l| l.1.as_str())).collect::<Vec<Option<&str>>>()
+            )?;
+            let mut file = fs::File::create(path)?;
+            CsvWriter::new(&mut file)
+                .has_header(true)
+                .finish(&mut quarantine)?;
+        }
+        let rows = typed.height();
+        REJECTIONS.lock().unwrap().push(Rejection {
+            path: self.path.clone(),
+            accepted: rows - rejected.len(),
+            rejected: rejected.len(),
+            quarantine: options.quarantine.clone(),
+        });
+        let fraction = match rows {
+            0 => 0.0,
+            rows => rejected.len() as f64 / rows as f64,
+        };
+        if let Some(max_rejected) = options.max_rejected {
+            if fraction > max_rejected {
+                return Err(PolarsError::ComputeError(
+                    format!(
+                        "{:.1}% of the rows of {} are rejected, more than the maximum of {:.1}%",
+                        fraction * 100.0,
+                        self.path,
+                        max_rejected * 100.0
+                    )
+                    .into(),
+                ));
+            }
+        }
+        let mut accepted = vec![true; rows];
+        for (i, _) in &rejected {
+            accepted[*i] = false;
+        }
+        let accepted: BooleanChunked = accepted.into_iter().collect();
+        Ok(typed.drop("__reason")?.filter(&accepted)?.lazy())
+    }
+}
+
+/// Number of fields of a record, the delimiters inside quotes are part of the field
+fn count_fields(record: &str, delimiter: u8, quote: Option<u8>) -> usize {
+    let mut quoted = false;
+    let mut fields = 1;
+    for b in record.bytes() {
+        if Some(b) == quote {
+            quoted = !quoted;
+        } else if b == delimiter && !quoted {
+            fields += 1;
+        }
+    }
+    fields
+}
+
+/// Is the text a number written with a decimal comma, e.g. -3,5 or 1.234,5?
+fn is_decimal_comma(text: &str) -> bool {
+    let text = text.trim().trim_start_matches(['-', '+']);
+    let (integer, decimals) = text.split_once(',').unwrap_or((text, ""));
+    !integer.is_empty()
+        && integer.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && decimals.chars().all(|c| c.is_ascii_digit())
+}
+
+impl Execute for ReadCsvStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let options = &self.options;
+        let dtypes = self.dtypes();
+        let path = decompress(&self.path)?;
+        let mut df = self
+            .reader(&path)
+            .with_dtype_overwrite(Some(&dtypes))
+            .with_infer_schema_length(options.infer_schema_length)
+            .with_parse_dates(options.parse_dates)
+            .with_ignore_parser_errors(options.ignore_errors)
             .finish()?;
+        if options.decimal_comma {
+            // the float columns given in dtypes and the string columns that look like numbers
+            let mut columns: Vec<(String, DataType)> = options
+                .dtypes
+                .iter()
+                .map(|(c, t)| (c.clone(), get_dtype(t, "us")))
+                .filter(|(_, dtype)| matches!(dtype, DataType::Float32 | DataType::Float64))
+                .collect();
+            for c in self.decimal_comma_columns(&path, &dtypes)? {
+                columns.push((c, DataType::Float64));
+            }
+            let numbers: Vec<Expr> = columns
+                .iter()
+                .map(|(c, dtype)| {
+                    col(c)
+                        .str()
+                        .replace_all(lit("."), lit(""), true)
+                        .str()
+                        .replace_all(lit(","), lit("."), true)
+                        .cast(dtype.clone())
+                        .alias(c)
+                })
+                .collect();
+            df = df.with_columns(numbers);
+        }
+        if options.quarantine.is_some() || options.max_rejected.is_some() {
+            df = self.reject_rows(&path, df)?;
+        }
+        if let Some(columns) = &options.columns {
+            df = df.select(get_array_columns(columns.clone()));
+        }
+        // a later limit (e.g. a preview) replaces the number of rows of the scan, so the rows are also limited here
+        if let Some(n_rows) = options.n_rows {
+            df = df.limit(n_rows as IdxSize);
+        }
         Ok(df)
     }
     fn validate(step: &Steps) {
         assert!(step.properties.contains_key("path"));
         assert!(step.properties.contains_key("delimiter"));
         assert!(step.properties.contains_key("header"));
+        let properties = &step.properties;
+        for name in ["skip_rows", "infer_schema_length", "n_rows"] {
+            if let Some(value) = properties.get(name) {
+                assert!(
+                    value.as_integer().is_some_and(|v| v >= 0),
+                    "Error! read_csv {} should be a non-negative integer",
+                    name
+                );
+            }
+        }
+        for name in ["comment", "quote"] {
+            if let Some(value) = properties.get(name) {
+                assert!(
+                    value.as_str().is_some_and(|v| v.len() <= 1),
+                    "Error! read_csv {} should be a single character",
+                    name
+                );
+            }
+        }
+        if let Some(comment) = properties.get("comment") {
+            assert!(
+                comment.as_str() != Some(""),
+                "Error! read_csv comment should be a single character"
+            );
+        }
+        for name in ["parse_dates", "ignore_errors", "decimal_comma"] {
+            if let Some(value) = properties.get(name) {
+                assert!(
+                    value.is_bool(),
+                    "Error! read_csv {} should be true or false",
+                    name
+                );
+            }
+        }
+        for name in ["null_values", "columns"] {
+            if let Some(value) = properties.get(name) {
+                assert!(
+                    is_columns(value),
+                    "Error! read_csv {} should be a string or a non-empty list of strings",
+                    name
+                );
+            }
+        }
+        if let Some(encoding) = properties.get("encoding") {
+            assert!(
+                encoding
+                    .as_str()
+                    .is_some_and(|e| e == "utf8" || e == "lossy"),
+                "Error! read_csv encoding should be utf8 or lossy"
+            );
+        }
+        if let Some(dtypes) = properties.get("dtypes") {
+            assert!(
+                dtypes.as_table().is_some_and(|d| d
+                    .values()
+                    .all(|t| t.as_str().is_some_and(|t| DATA_TYPES.contains(&t)))),
+                "Error! read_csv dtypes should be a table of columns and their types, one of {:?}",
+                DATA_TYPES
+            );
+        }
+        if let Some(quarantine) = properties.get("quarantine") {
+            assert!(
+                quarantine.is_str(),
+                "Error! read_csv quarantine should be the path of a csv file"
+            );
+        }
+        if let Some(max_rejected) = properties.get("max_rejected") {
+            assert!(
+                (max_rejected.is_integer() || max_rejected.is_float())
+                    && (0.0..=1.0).contains(&get_number(max_rejected)),
+                "Error! read_csv max_rejected should be a fraction between 0 and 1"
+            );
+        }
+        if properties.contains_key("quarantine") || properties.contains_key("max_rejected") {
+            assert!(
+                properties.get("ignore_errors").and_then(|v| v.as_bool()) != Some(false),
+                "Error! read_csv cannot reject rows when ignore_errors is false"
+            );
+        }
     }
 }
 
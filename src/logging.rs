@@ -0,0 +1,130 @@
+// oxidf logging of the pipeline runs
+// Author: David Issa Mattos
+// Mantainer: David Issa Mattos
+
+use chrono::Utc;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::panic;
+use std::sync::Mutex;
+
+/// Amount of messages shown while running
+/// * quiet: only warnings and errors
+/// * normal: the main messages of the run
+/// * verbose: also every step as it runs
+/// * debug: everything, including the internal details of the steps
+#[derive(Clone, Copy)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+    Debug,
+}
+impl Verbosity {
+    fn level(&self) -> LevelFilter {
+        match self {
+            Verbosity::Quiet => LevelFilter::Warn,
+            Verbosity::Normal => LevelFilter::Info,
+            Verbosity::Verbose => LevelFilter::Debug,
+            Verbosity::Debug => LevelFilter::Trace,
+        }
+    }
+}
+
+thread_local! {
+    // step being executed by the current thread, added to every message
+    static STEP: RefCell<Option<(usize, String)>> = const { RefCell::new(None) };
+}
+
+/// Set the step executed by the current thread
+/// Every step runs in its own thread, so the messages logged while running it carry its context
+pub fn set_step(step: Option<(usize, &str)>) {
+    STEP.with(|s| *s.borrow_mut() = step.map(|(i, op)| (i, op.to_string())));
+}
+
+/// Logger writing to stderr, so that stdout only carries data, and optionally to a JSON lines file
+struct Logger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+}
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let timestamp = Utc::now();
+        let step = STEP.with(|s| s.borrow().clone());
+        let context = match &step {
+            Some((i, op)) => format!("[step {} {}] ", i, op),
+            None => String::new(),
+        };
+        eprintln!(
+            "{} {:<5} {}{}",
+            timestamp.format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            context,
+            record.args()
+        );
+        if let Some(file) = &self.file {
+            let line = serde_json::json!({
+                "timestamp": timestamp.to_rfc3339(),
+                "level": record.level().as_str(),
+                "step": step.as_ref().map(|(i, _)| i),
+                "operation": step.as_ref().map(|(_, op)| op),
+                "message": record.args().to_string(),
+            });
+            let mut file = file.lock().unwrap();
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Initialize the logging
+/// Errors (panics) are also logged, so they end up in the log file with the step where they happened
+/// Arguments:
+/// * the verbosity of the messages
+/// * optional path of a file where the messages are appended as JSON lines
+pub fn init_logging(verbosity: Verbosity, log_file: Option<&str>) -> Result<(), String> {
+    let file = match log_file {
+        Some(path) => Some(Mutex::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Cannot open the log file {}: {}", path, e))?,
+        )),
+        None => None,
+    };
+    let level = verbosity.level();
+    log::set_boxed_logger(Box::new(Logger { level, file })).map_err(|e| e.to_string())?;
+    log::set_max_level(level);
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let message = match info.payload().downcast_ref::<String>() {
+            Some(s) => s.clone(),
+            None => info
+                .payload()
+                .downcast_ref::<&str>()
+                .map_or(String::from("unknown error"), |s| s.to_string()),
+        };
+        log::error!("{}", message);
+        // in debug mode we also show where the error happened
+        if log::log_enabled!(Level::Trace) {
+            default_hook(info);
+        }
+    }));
+    Ok(())
+}
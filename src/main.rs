@@ -1,39 +1,169 @@
 // oxidf main application
 // Author: David Issa Mattos
 // Mantainer: David Issa Mattos
+mod catalog;
+mod compression;
 mod compute;
-mod merge;
+mod graph;
 mod input;
+mod logging;
+mod merge;
 mod operations;
 mod output;
+mod params;
 mod parser;
+mod profile;
+mod scaffold;
 mod steps;
+mod summary;
 mod utils;
-use clap::Parser;
-use compute::compute_pipeline;
+use catalog::print_operations;
+use clap::{Args, Parser, Subcommand};
+use compute::{compute_pipeline, explain_pipeline, RunOptions};
+use log::{debug, info};
+use logging::{init_logging, Verbosity};
 use parser::*;
+use scaffold::scaffold_pipeline;
+use std::fs;
+use std::path::Path;
 
 #[derive(Parser)]
 #[command(author, version, about)]
-struct CLI {
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    // only show warnings and errors
+    #[clap(short = 'q', long, global = true, action)]
+    quiet: bool,
+    // also show every step as it runs
+    #[clap(short = 'v', long, global = true, action)]
+    verbose: bool,
+    // show everything, including internal details and where errors happen
+    #[clap(long, global = true, action)]
+    debug: bool,
+    // append the messages to this file as JSON lines
+    #[clap(long, global = true)]
+    log_file: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a pipeline
+    Run {
+        #[command(flatten)]
+        pipeline: PipelineArgs,
+        // print the logical plans before every output step
+        #[clap(long, action)]
+        explain: bool,
+        // print the time, rows, columns and memory of every step at the end of the run
+        #[clap(long, action)]
+        profile: bool,
+        // collect the frame at the end of every step to count its rows. Implies --profile
+        #[clap(long, action)]
+        profile_collect: bool,
+        // save the profile in a JSON file. Implies --profile
+        #[clap(long)]
+        profile_json: Option<String>,
+    },
+    /// Parse and validate a pipeline without running it
+    Validate {
+        #[command(flatten)]
+        pipeline: PipelineArgs,
+    },
+    /// List the available operations with their properties and an example
+    Ops {
+        // show only this operation
+        operation: Option<String>,
+    },
+    /// Print the query plans of every output of a pipeline
+    Explain {
+        #[command(flatten)]
+        pipeline: PipelineArgs,
+        // folder where the optimized plans are written as Graphviz DOT files
+        #[clap(long)]
+        dot: Option<String>,
+    },
+    /// Create a pipeline for a csv file, sniffing its delimiter and columns
+    Init {
+        // path to the csv file
+        csv: String,
+        // path of the TOML file to create. Printed in the terminal if not given
+        #[clap(short = 'o', long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Args)]
+struct PipelineArgs {
     // path to the TOML file
     #[clap(short = 'p', long)]
     path: String,
-    // if running on verbose mode or not
-    #[clap(short = 'v', long, action)]
-    verbose: bool,
+    // values of the pipeline parameters, as key=value. Can be repeated
+    #[clap(long, value_name = "KEY=VALUE")]
+    param: Vec<String>,
+}
+impl PipelineArgs {
+    fn load(&self) -> steps::Pipeline {
+        debug!("Reading toml file: {}", self.path);
+        let pipeline = load_pipeline(self.path.as_str(), &self.param)
+            .unwrap_or_else(|e| panic!("Error! {}", e));
+        validate_pipeline(&pipeline);
+        pipeline
+    }
 }
 
 fn main() {
-    let cli = CLI::parse();
+    let cli = Cli::parse();
+    let verbosity = match (cli.debug, cli.verbose, cli.quiet) {
+        (true, _, _) => Verbosity::Debug,
+        (_, true, _) => Verbosity::Verbose,
+        (_, _, true) => Verbosity::Quiet,
+        _ => Verbosity::Normal,
+    };
+    init_logging(verbosity, cli.log_file.as_deref()).unwrap_or_else(|e| panic!("Error! {}", e));
 
-    let path = cli.path;
-    let messages =  cli.verbose;
-    if messages {
-        println!("Reading toml file: {}", path);
+    match cli.command {
+        Command::Run {
+            pipeline,
+            explain,
+            profile,
+            profile_collect,
+            profile_json,
+        } => {
+            let pipeline = pipeline.load();
+            let options = RunOptions {
+                explain,
+                profile: profile || profile_collect || profile_json.is_some(),
+                profile_collect,
+                profile_json,
+            };
+            compute_pipeline(&pipeline, &options);
+        }
+        Command::Validate { pipeline } => {
+            let path = pipeline.path.clone();
+            let pipeline = pipeline.load();
+            info!("{} is valid: {} steps", path, pipeline.steps.len());
+        }
+        Command::Ops { operation } => print_operations(operation.as_deref()),
+        Command::Explain { pipeline, dot } => {
+            let pipeline = pipeline.load();
+            explain_pipeline(&pipeline, dot.as_deref());
+        }
+        Command::Init { csv, output } => {
+            let toml = scaffold_pipeline(csv.as_str()).unwrap_or_else(|e| panic!("Error! {}", e));
+            match output {
+                None => print!("{}", toml),
+                Some(output) => {
+                    assert!(
+                        !Path::new(&output).exists(),
+                        "Error! {} already exists",
+                        output
+                    );
+                    fs::write(&output, toml)
+                        .unwrap_or_else(|e| panic!("Error writing {}: {}", output, e));
+                    info!("Created {}", output);
+                }
+            }
+        }
     }
-
-    let pipeline = parse_toml(path.as_str()).expect("Error parsing the toml file");
-    validate_pipeline(&pipeline);
-    compute_pipeline(&pipeline, messages);
 }
@@ -6,6 +6,12 @@ use crate::steps::*;
 use crate::utils::*;
 use polars::prelude::*;
 use polars_ops::pivot::{pivot, PivotAgg};
+use log::warn;
+use rand::Rng;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::Mutex;
 use toml::Value;
 
 pub const OPERATIONS_OP: &[&str] = &[
@@ -22,8 +28,22 @@ pub const OPERATIONS_OP: &[&str] = &[
     "recode",
     "cast",
     "anonymize",
+    "mask_keep_last",
+    "mask_email",
+    "mask_regex",
+    "mask_range",
+    "mask_date",
     "pivot",
-    "unique"
+    "unique",
+    "sample",
+    "head",
+    "tail",
+    "slice",
+    "window",
+    "rolling",
+    "resample",
+    "bin",
+    "encode"
 ];
 
 /// Rename column
@@ -36,9 +56,9 @@ pub struct RenameStep {
 impl RenameStep {
     pub fn new(lazydf: LazyFrame, col: String, name: String) -> Self {
         RenameStep {
-            lazydf: lazydf,
-            col: col,
-            name: name,
+            lazydf,
+            col,
+            name,
         }
     }
     pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
@@ -85,8 +105,8 @@ pub struct SelectColumnsStep {
 impl SelectColumnsStep {
     pub fn new(lazydf: LazyFrame, columns: Vec<String>) -> Self {
         SelectColumnsStep {
-            lazydf: lazydf,
-            columns: columns,
+            lazydf,
+            columns,
         }
     }
     pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
@@ -290,7 +310,7 @@ impl FilterEqualStep {
 impl Execute for FilterEqualStep {
     fn execute(&self) -> Result<LazyFrame, PolarsError> {
         let expr: Expr = col(self.col.as_str());
-        let mut new_lazydf: LazyFrame;
+        let new_lazydf: LazyFrame;
         if self.value.is_str() {
             let v = self.value.as_str().unwrap();
             new_lazydf = self.lazydf.clone().filter(expr.eq(lit(v)));
@@ -350,8 +370,8 @@ impl FilterIsInStep {
 impl Execute for FilterIsInStep {
     fn execute(&self) -> Result<LazyFrame, PolarsError> {
         let expr: Expr = col(self.col.as_str());
-        let first_item = self.value_list.get(0).unwrap().clone();
-        let mut new_lazydf: LazyFrame;
+        let first_item = self.value_list.first().unwrap().clone();
+        let new_lazydf: LazyFrame;
         if first_item.is_str() {
             let v = get_string_array(self.value_list.clone());
             let s = lit(Series::new("_", v));
@@ -382,40 +402,98 @@ impl Execute for FilterIsInStep {
 }
 
 /// anonymize
-/// Anonymize a string column
+/// Anonymize one or more columns by hashing their values
+/// The hash is a lazy expression, so the data is not collected and the following filters can still be optimized
 pub struct AnonymizeStep {
     lazydf: LazyFrame,
-    col: String,
+    cols: Vec<String>,
+    hasher: Hasher,
+    mapping: Option<String>,
 }
 impl AnonymizeStep {
-    pub fn new(lazydf: LazyFrame, col: String) -> Self {
+    pub fn new(
+        lazydf: LazyFrame,
+        cols: Vec<String>,
+        hasher: Hasher,
+        mapping: Option<String>,
+    ) -> Self {
         AnonymizeStep {
-            lazydf: lazydf,
-            col: col,
+            lazydf,
+            cols,
+            hasher,
+            mapping,
         }
     }
     pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
-        let col = step
-            .properties
-            .get("col")
-            .unwrap()
-            .as_str()
-            .unwrap()
-            .to_string();
-        AnonymizeStep::new(lazydf, col)
+        // col can be a single column or a list of columns
+        let cols = get_columns(step.properties.get("col").unwrap());
+        let get_str = |name: &str| {
+            step.properties
+                .get(name)
+                .map(|v| v.as_str().unwrap().to_string())
+        };
+        // the key is never written in the pipeline, it is read from a file or an environment variable
+        let key = match (get_str("key_file"), get_str("key_env")) {
+            (Some(path), _) => Some(
+                fs::read_to_string(&path)
+                    .unwrap_or_else(|e| panic!("Error! Cannot read the key file {}: {}", path, e))
+                    .trim_end_matches(['\n', '\r'])
+                    .to_string(),
+            ),
+            (None, Some(var)) => Some(env::var(&var).unwrap_or_else(|_| {
+                panic!("Error! The environment variable {} with the key is not set", var)
+            })),
+            (None, None) => None,
+        };
+        let hasher = Hasher {
+            algorithm: get_str("algorithm").unwrap_or_else(|| String::from("sha256")),
+            salt: get_str("salt").unwrap_or_default(),
+            key: key.map(String::into_bytes),
+            length: step
+                .properties
+                .get("length")
+                .map(|v| v.as_integer().unwrap() as usize),
+        };
+        AnonymizeStep::new(lazydf, cols, hasher, get_str("mapping"))
     }
 }
 impl Execute for AnonymizeStep {
     fn execute(&self) -> Result<LazyFrame, PolarsError> {
-        let mut df = self
-            .lazydf
-            .clone()
-            .collect()
-            .unwrap()
-            .apply(self.col.as_str(), hash_polars)
-            .unwrap()
-            .clone();
-        let new_lazydf = df.lazy();
+        let mut lazydf = self.lazydf.clone();
+        if let Some(path) = &self.mapping {
+            // the mapping is saved from the values that reach this step when the frame is collected,
+            // so building the plan (e.g. when explaining) never writes it
+            let cols = self.cols.clone();
+            let hasher = self.hasher.clone();
+            let path = path.clone();
+            let mapping: Arc<Mutex<DataFrame>> = Arc::default();
+            // all the columns reach the mapping, even the ones dropped by later steps
+            let optimizations = AllowedOptimizations {
+                projection_pushdown: false,
+                ..Default::default()
+            };
+            lazydf = lazydf.map(
+                move |df| {
+                    save_mapping(&df, &cols, &hasher, &path, &mapping)?;
+                    Ok(df)
+                },
+                Some(optimizations),
+                None,
+                Some("anonymize mapping"),
+            );
+        }
+        let hashed_cols: Vec<Expr> = self
+            .cols
+            .iter()
+            .map(|c| {
+                let hasher = self.hasher.clone();
+                col(c).map(
+                    move |s| hasher.hash_series(s),
+                    GetOutput::from_type(DataType::Utf8),
+                )
+            })
+            .collect();
+        let new_lazydf = lazydf.with_columns(hashed_cols);
         Ok(new_lazydf)
     }
     fn validate(step: &Steps) {
@@ -423,19 +501,102 @@ impl Execute for AnonymizeStep {
             step.properties.contains_key("col"),
             "Error! anonymize does not contain property: col"
         );
+        assert!(
+            is_columns(step.properties.get("col").unwrap()),
+            "Error! anonymize col should be a column name or a non-empty list of column names"
+        );
+        for name in ["algorithm", "salt", "key_file", "key_env", "mapping"] {
+            if let Some(value) = step.properties.get(name) {
+                assert!(value.is_str(), "Error! anonymize {} should be a string", name);
+            }
+        }
+        if let Some(algorithm) = step.properties.get("algorithm") {
+            let algorithm = algorithm.as_str().unwrap();
+            assert!(
+                HASH_ALGORITHMS.contains(&algorithm),
+                "Error! anonymize algorithm should be one of {:?}",
+                HASH_ALGORITHMS
+            );
+        }
+        assert!(
+            !(step.properties.contains_key("key_file") && step.properties.contains_key("key_env")),
+            "Error! anonymize accepts only one of key_file and key_env"
+        );
+        if let Some(length) = step.properties.get("length") {
+            assert!(
+                length.as_integer().is_some_and(|l| l > 0),
+                "Error! anonymize length should be a positive integer"
+            );
+        }
     }
 }
 
-/// filter_contains
-/// filter a string column based on a regex expression
-pub struct FilterContainsStep {
+/// Save the table of original values and their pseudonyms
+/// The values of every collect of the frame are added to the ones already saved, e.g. when a preview
+/// reads some rows and a save_csv all of them
+/// Arguments:
+/// * the frame with the original values
+/// * the anonymized columns and their hasher
+/// * the path of the mapping file
+/// * the mapping saved so far
+fn save_mapping(
+    df: &DataFrame,
+    cols: &[String],
+    hasher: &Hasher,
+    path: &str,
+    mapping: &Mutex<DataFrame>,
+) -> Result<(), PolarsError> {
+    let mut mapping = mapping.lock().unwrap();
+    for c in cols {
+        let original = df
+            .select([c.as_str()])?
+            .unique_stable(None, UniqueKeepStrategy::First)?;
+        let mut original = original.column(c)?.cast(&DataType::Utf8)?;
+        original.rename("original");
+        let pseudonym = hasher.hash_series(original.clone())?;
+        let new = df!(
+            "column" => vec![c.as_str(); original.len()],
+            "original" => original,
+            "pseudonym" => pseudonym,
+        )?;
+        mapping.vstack_mut(&new)?;
+    }
+    *mapping = mapping.unique_stable(None, UniqueKeepStrategy::First)?;
+    // the mapping allows re-identification, so only the owner can read it
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .unwrap_or_else(|e| panic!("Error! Cannot create the mapping file {}: {}", path, e));
+    // the mode is only given to new files, a file that existed before keeps its permissions until they are changed
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if file.metadata()?.permissions().mode() & 0o077 != 0 {
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    CsvWriter::new(&mut file).finish(&mut mapping)
+}
+
+/// mask_keep_last
+/// Mask all the characters of a column except the last ones (e.g. the last 4 digits of an ID)
+pub struct MaskKeepLastStep {
     lazydf: LazyFrame,
     col: String,
-    value: String,
+    keep: usize,
+    mask_char: char,
 }
-impl FilterContainsStep {
-    pub fn new(lazydf: LazyFrame, col: String, value: String) -> Self {
-        FilterContainsStep { lazydf, col, value }
+impl MaskKeepLastStep {
+    pub fn new(lazydf: LazyFrame, col: String, keep: usize, mask_char: char) -> Self {
+        MaskKeepLastStep {
+            lazydf,
+            col,
+            keep,
+            mask_char,
+        }
     }
     pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
         let col = step
@@ -445,40 +606,63 @@ impl FilterContainsStep {
             .as_str()
             .unwrap()
             .to_string();
-        let value = step
-            .properties
-            .get("value")
-            .unwrap()
-            .as_str()
-            .unwrap()
-            .to_string();
-        FilterContainsStep::new(lazydf, col, value)
+        let keep = step.properties.get("keep").unwrap().as_integer().unwrap() as usize;
+        MaskKeepLastStep::new(lazydf, col, keep, get_mask_char(step))
     }
 }
-impl Execute for FilterContainsStep {
+impl Execute for MaskKeepLastStep {
     fn execute(&self) -> Result<LazyFrame, PolarsError> {
-        let expr: Expr = col(self.col.as_str()).str().contains(self.value.clone());
-        let new_lazydf = self.lazydf.clone().filter(expr);
+        let keep = self.keep;
+        let mask_char = self.mask_char;
+        let masked = col(self.col.as_str()).map(
+            move |s| {
+                map_utf8(s, |value| {
+                    let n_masked = value.chars().count().saturating_sub(keep);
+                    value
+                        .chars()
+                        .enumerate()
+                        .map(|(i, c)| if i < n_masked { mask_char } else { c })
+                        .collect()
+                })
+            },
+            GetOutput::from_type(DataType::Utf8),
+        );
+        let new_lazydf = self.lazydf.clone().with_column(masked);
         Ok(new_lazydf)
     }
     fn validate(step: &Steps) {
+        validate_mask_col(step);
         assert!(
-            step.properties.contains_key("col"),
-            "Error filter_contains does not contain property: col"
+            step.properties.contains_key("keep"),
+            "Error! mask_keep_last does not contain property: keep"
+        );
+        assert!(
+            step.properties
+                .get("keep")
+                .unwrap()
+                .as_integer()
+                .is_some_and(|k| k >= 0),
+            "Error! mask_keep_last keep should be a non-negative integer"
         );
-        assert!(step.properties.contains_key("value"));
     }
 }
 
-///remove_na
-/// Remove rows that are NA in a specific column
-pub struct RemoveNAStep {
+/// mask_email
+/// Mask the local part of e-mail addresses (before the @), keeping the domain
+pub struct MaskEmailStep {
     lazydf: LazyFrame,
     col: String,
+    keep: usize,
+    mask_char: char,
 }
-impl RemoveNAStep {
-    pub fn new(lazydf: LazyFrame, col: String) -> Self {
-        RemoveNAStep { lazydf, col }
+impl MaskEmailStep {
+    pub fn new(lazydf: LazyFrame, col: String, keep: usize, mask_char: char) -> Self {
+        MaskEmailStep {
+            lazydf,
+            col,
+            keep,
+            mask_char,
+        }
     }
     pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
         let col = step
@@ -488,38 +672,64 @@ impl RemoveNAStep {
             .as_str()
             .unwrap()
             .to_string();
-        RemoveNAStep::new(lazydf, col)
+        let keep = step
+            .properties
+            .get("keep")
+            .map_or(0, |k| k.as_integer().unwrap() as usize);
+        MaskEmailStep::new(lazydf, col, keep, get_mask_char(step))
     }
 }
-impl Execute for RemoveNAStep {
+impl Execute for MaskEmailStep {
     fn execute(&self) -> Result<LazyFrame, PolarsError> {
-        let expr = col(self.col.as_str());
-        let new_lazydf = self.lazydf.clone().filter(expr.is_not_null());
+        let keep = self.keep;
+        let mask_char = self.mask_char;
+        let masked = col(self.col.as_str()).map(
+            move |s| {
+                map_utf8(s, |value| {
+                    // values that are not e-mails are masked completely
+                    let (local, domain) = match value.rfind('@') {
+                        Some(at) => value.split_at(at),
+                        None => (value, ""),
+                    };
+                    let masked_local: String = local
+                        .chars()
+                        .enumerate()
+                        .map(|(i, c)| if i < keep { c } else { mask_char })
+                        .collect();
+                    format!("{}{}", masked_local, domain)
+                })
+            },
+            GetOutput::from_type(DataType::Utf8),
+        );
+        let new_lazydf = self.lazydf.clone().with_column(masked);
         Ok(new_lazydf)
     }
     fn validate(step: &Steps) {
-        assert!(
-            step.properties.contains_key("col"),
-            "Error! remove_na does not contain property: col"
-        );
+        validate_mask_col(step);
+        if let Some(keep) = step.properties.get("keep") {
+            assert!(
+                keep.as_integer().is_some_and(|k| k >= 0),
+                "Error! mask_email keep should be a non-negative integer"
+            );
+        }
     }
 }
 
-/// recode
-/// recode items of a column
-pub struct RecodeStep {
+/// mask_regex
+/// Replace every match of a regex pattern in a string column (e.g. phone numbers in free text)
+pub struct MaskRegexStep {
     lazydf: LazyFrame,
     col: String,
-    from: Vec<Value>,
-    to: Vec<Value>,
+    pattern: String,
+    replacement: String,
 }
-impl RecodeStep {
-    pub fn new(lazydf: LazyFrame, col: String, from: Vec<Value>, to: Vec<Value>) -> Self {
-        RecodeStep {
+impl MaskRegexStep {
+    pub fn new(lazydf: LazyFrame, col: String, pattern: String, replacement: String) -> Self {
+        MaskRegexStep {
             lazydf,
             col,
-            from,
-            to,
+            pattern,
+            replacement,
         }
     }
     pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
@@ -530,169 +740,146 @@ impl RecodeStep {
             .as_str()
             .unwrap()
             .to_string();
-
-        let from = step
+        let pattern = step
             .properties
-            .get("from")
+            .get("pattern")
             .unwrap()
-            .as_array()
+            .as_str()
             .unwrap()
-            .clone();
+            .to_string();
+        let replacement = step
+            .properties
+            .get("replacement")
+            .map_or(String::from("***"), |r| r.as_str().unwrap().to_string());
+        MaskRegexStep::new(lazydf, col, pattern, replacement)
+    }
+}
+impl Execute for MaskRegexStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let masked = col(self.col.as_str()).cast(DataType::Utf8).str().replace_all(
+            lit(self.pattern.as_str()),
+            lit(self.replacement.as_str()),
+            false,
+        );
+        let new_lazydf = self.lazydf.clone().with_column(masked);
+        Ok(new_lazydf)
+    }
+    fn validate(step: &Steps) {
+        validate_mask_col(step);
+        assert!(
+            step.properties.get("pattern").is_some_and(|p| p.is_str()),
+            "Error! mask_regex does not contain property: pattern"
+        );
+        if let Some(replacement) = step.properties.get("replacement") {
+            assert!(
+                replacement.is_str(),
+                "Error! mask_regex replacement should be a string"
+            );
+        }
+    }
+}
 
-        let to = step
+/// mask_range
+/// Generalize a numeric column into ranges of a given width, e.g. 43 -> "[40, 50)"
+pub struct MaskRangeStep {
+    lazydf: LazyFrame,
+    col: String,
+    width: f64,
+    origin: f64,
+    // are the limits of the ranges integers?
+    integer: bool,
+}
+impl MaskRangeStep {
+    pub fn new(lazydf: LazyFrame, col: String, width: f64, origin: f64, integer: bool) -> Self {
+        MaskRangeStep {
+            lazydf,
+            col,
+            width,
+            origin,
+            integer,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let col = step
             .properties
-            .get("to")
+            .get("col")
             .unwrap()
-            .as_array()
+            .as_str()
             .unwrap()
-            .clone();
-        RecodeStep::new(lazydf, col, from, to)
+            .to_string();
+        let width = step.properties.get("width").unwrap();
+        let origin = step.properties.get("origin");
+        let integer = width.is_integer() && origin.is_none_or(|o| o.is_integer());
+        MaskRangeStep::new(
+            lazydf,
+            col,
+            get_number(width),
+            origin.map_or(0.0, get_number),
+            integer,
+        )
     }
 }
-impl Execute for RecodeStep {
+impl Execute for MaskRangeStep {
     fn execute(&self) -> Result<LazyFrame, PolarsError> {
-        let first_to = self.to.get(0).unwrap().clone();
-        let first_from = self.from.get(0).unwrap().clone();
-        let mut lazydf = self.lazydf.clone();
-
-        if first_from.is_str() & first_to.is_str() {
-            let from = get_string_array(self.from.clone());
-            let to = get_string_array(self.to.clone());
-            // println!("{:?}",from);
-            for (pos, v_from) in from.iter().enumerate() {
-                let v_to = to.get(pos).unwrap().as_str();
-                let v_from = v_from.as_str();
-                lazydf = lazydf.with_column(
-                    when(col(self.col.as_str()).eq(lit(v_from)))
-                        .then(lit(v_to))
-                        .otherwise(col(self.col.as_str()))
-                        .alias(self.col.as_str()),
-                );
-            }
-        } else if first_from.is_float() & first_to.is_float() {
-            let from = get_float_array(self.from.clone());
-            let to = get_float_array(self.from.clone());
-            for (pos, v_from) in from.iter().enumerate() {
-                let v_to = to.get(pos).unwrap().clone();
-                let v_from = v_from.clone();
-                lazydf = lazydf.with_column(
-                    when(col(self.col.as_str()).eq(lit(v_from)))
-                        .then(lit(v_to))
-                        .otherwise(col(self.col.as_str()))
-                        .alias(self.col.as_str()),
-                );
-            }
-        } else if first_from.is_integer() & first_to.is_integer() {
-            let from = get_int_array(self.from.clone());
-            let to = get_int_array(self.from.clone());
-            for (pos, v_from) in from.iter().enumerate() {
-                let v_to = to.get(pos).unwrap().clone();
-                let v_from = v_from.clone();
-                lazydf = lazydf.with_column(
-                    when(col(self.col.as_str()).eq(lit(v_from)))
-                        .then(lit(v_to))
-                        .otherwise(col(self.col.as_str()))
-                        .alias(self.col.as_str()),
-                );
-            }
-        } else if first_from.is_integer() & first_to.is_str() {
-            let from = get_int_array(self.from.clone());
-            let to = get_string_array(self.to.clone());
-            for (pos, v_from) in from.iter().enumerate() {
-                let v_to = to.get(pos).unwrap().as_str();
-                let v_from = v_from.clone(); //.to_string().as_str(); //convert int to string
-                lazydf = lazydf.with_column(
-                    when(col(self.col.as_str()).eq(lit(v_from)))
-                        .then(lit(v_to))
-                        .otherwise(col(self.col.as_str()))
-                        .alias(self.col.as_str()),
-                );
-                //we cast after replacing
-                lazydf = lazydf.with_column(
-                    col(self.col.as_str())
-                        .cast(DataType::Utf8)
-                        .alias(self.col.as_str()),
-                );
-            }
-        } else if first_from.is_str() & first_to.is_integer() {
-            let from = get_string_array(self.from.clone());
-            let to = get_int_array(self.to.clone());
-            for (pos, v_from) in from.iter().enumerate() {
-                let v_to = to.get(pos).unwrap().clone();
-                let v_from = v_from.clone(); //.to_string().as_str(); //convert int to string
-                lazydf = lazydf.with_column(
-                    when(col(self.col.as_str()).eq(lit(v_from.as_str())))
-                        .then(lit(v_to))
-                        .otherwise(col(self.col.as_str()))
-                        .alias(self.col.as_str()),
-                );
-            }
-            //we cast after replacing
-            lazydf = lazydf.with_column(
-                col(self.col.as_str())
-                    .cast(DataType::Int64)
-                    .alias(self.col.as_str()),
-            );
-        } else {
-            panic!("Error! In recode step. The lists to and from do not match correct types")
-        }
-        let new_lazydf = lazydf.clone();
+        let lower = ((col(self.col.as_str()).cast(DataType::Float64) - lit(self.origin))
+            / lit(self.width))
+        .floor()
+            * lit(self.width)
+            + lit(self.origin);
+        let upper = lower.clone() + lit(self.width);
+        let (lower, upper) = match self.integer {
+            true => (lower.cast(DataType::Int64), upper.cast(DataType::Int64)),
+            false => (lower, upper),
+        };
+        let masked = concat_str(
+            [
+                lit("["),
+                lower.cast(DataType::Utf8),
+                lit(", "),
+                upper.cast(DataType::Utf8),
+                lit(")"),
+            ],
+            "",
+        )
+        .alias(self.col.as_str());
+        let new_lazydf = self.lazydf.clone().with_column(masked);
         Ok(new_lazydf)
     }
     fn validate(step: &Steps) {
+        validate_mask_col(step);
+        let is_number = |v: &Value| v.is_integer() || v.is_float();
         assert!(
-            step.properties.contains_key("col"),
-            "Error! recode does not contain property: col"
-        );
-        assert!(
-            step.properties.contains_key("to"),
-            "Error! recode does not contain property: to"
+            step.properties.get("width").is_some_and(is_number),
+            "Error! mask_range does not contain a numeric property: width"
         );
+        let width = step.properties.get("width").unwrap();
         assert!(
-            step.properties.contains_key("from"),
-            "Error! recode does not contain property: from"
+            get_number(width) > 0.0,
+            "Error! mask_range width should be positive"
         );
-        let to = step
-            .properties
-            .get("to")
-            .unwrap()
-            .as_array()
-            .unwrap()
-            .clone();
-        let from = step
-            .properties
-            .get("from")
-            .unwrap()
-            .as_array()
-            .unwrap()
-            .clone();
-        let first_to = to.get(0).unwrap().clone();
-        let first_from = from.get(0).unwrap().clone();
-        if first_from.is_integer() {
-            let _ = get_int_array(to.clone());
-            let _ = get_int_array(from.clone());
-        }
-        if first_from.is_float() {
-            let _ = get_float_array(to.clone());
-            let _ = get_float_array(from.clone());
-        }
-        if first_from.is_float() {
-            let _ = get_float_array(to.clone());
-            let _ = get_float_array(from.clone());
+        if let Some(origin) = step.properties.get("origin") {
+            assert!(is_number(origin), "Error! mask_range origin should be a number");
         }
     }
 }
 
-/// cast
-/// cast a column into string, int or float
-pub struct CastStep {
+/// mask_date
+/// Coarsen a date column to its month ("2020-05") or year ("2020")
+pub struct MaskDateStep {
     lazydf: LazyFrame,
     col: String,
     to: String,
+    // format used to parse string columns
+    format: String,
 }
-impl CastStep {
-    pub fn new(lazydf: LazyFrame, col: String, to: String) -> Self {
-        CastStep { lazydf, col, to }
+impl MaskDateStep {
+    pub fn new(lazydf: LazyFrame, col: String, to: String, format: String) -> Self {
+        MaskDateStep {
+            lazydf,
+            col,
+            to,
+            format,
+        }
     }
     pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
         let col = step
@@ -709,105 +896,2080 @@ impl CastStep {
             .as_str()
             .unwrap()
             .to_string();
-        CastStep::new(lazydf, col, to)
+        let format = step
+            .properties
+            .get("format")
+            .map_or(String::from("%Y-%m-%d"), |f| f.as_str().unwrap().to_string());
+        MaskDateStep::new(lazydf, col, to, format)
     }
 }
-impl Execute for CastStep {
+impl Execute for MaskDateStep {
     fn execute(&self) -> Result<LazyFrame, PolarsError> {
-        let dtype = match self.to.as_str() {
-            "string" => DataType::Utf8,
-            "int" => DataType::Int64,
-            "float" => DataType::Float64,
-            _ => panic!("Error! {} is not a valid data type", self.to.as_str()),
+        let output_format = match self.to.as_str() {
+            "month" => "%Y-%m",
+            _ => "%Y",
         };
-        let new_lazydf = self
-            .lazydf
-            .clone()
-            .with_column(col(self.col.as_str()).cast(dtype).alias(self.col.as_str()));
+        let format = self.format.clone();
+        let masked = col(self.col.as_str()).map(
+            move |s| {
+                let mut masked = match s.dtype() {
+                    DataType::Date => s.date()?.strftime(output_format),
+                    DataType::Datetime(_, _) => s.datetime()?.strftime(output_format),
+                    DataType::Utf8 => s.utf8()?.as_date(Some(&format))?.strftime(output_format),
+                    dtype => {
+                        return Err(PolarsError::ComputeError(
+                            format!("mask_date cannot mask a column of type {}", dtype).into(),
+                        ))
+                    }
+                };
+                masked.rename(s.name());
+                Ok(masked.into_series())
+            },
+            GetOutput::from_type(DataType::Utf8),
+        );
+        let new_lazydf = self.lazydf.clone().with_column(masked);
         Ok(new_lazydf)
     }
     fn validate(step: &Steps) {
+        validate_mask_col(step);
         assert!(
-            step.properties.contains_key("col"),
-            "Error! cast does not contain property: col"
+            step.properties
+                .get("to")
+                .and_then(|t| t.as_str())
+                .is_some_and(|t| t == "month" || t == "year"),
+            "Error! mask_date to should be month or year"
         );
+        if let Some(format) = step.properties.get("format") {
+            assert!(format.is_str(), "Error! mask_date format should be a string");
+        }
+    }
+}
+
+// character used to mask the values, * by default
+fn get_mask_char(step: &Steps) -> char {
+    step.properties
+        .get("mask_char")
+        .map_or('*', |c| c.as_str().unwrap().chars().next().unwrap())
+}
+
+// every mask operation needs a column, and optionally a single mask character
+fn validate_mask_col(step: &Steps) {
+    assert!(
+        step.properties.get("col").is_some_and(|c| c.is_str()),
+        "Error! {} does not contain property: col",
+        step.operation
+    );
+    if let Some(mask_char) = step.properties.get("mask_char") {
         assert!(
-            step.properties.contains_key("to"),
-            "Error! cast does not contain property: to"
+            mask_char.as_str().is_some_and(|c| c.chars().count() == 1),
+            "Error! {} mask_char should be a single character",
+            step.operation
         );
     }
 }
 
-/// Pivot table
-/// pivot a table from long to wide format
-pub struct PivotStep {
+/// sample
+/// Random sample of the rows, a fixed number or a fraction of them, optionally stratified by a column
+/// The rows are drawn from the collected data, keeping their order
+pub struct SampleStep {
     lazydf: LazyFrame,
-    values: Vec<String>,
-    index: Vec<String>,
-    columns: Vec<String>,
-    aggregation: String,
-    sort_columns: bool,
+    size: SampleSize,
+    replace: bool,
+    seed: Option<u64>,
+    stratify: Option<String>,
 }
-impl PivotStep {
+/// Number of rows drawn by the sample, in every stratum if stratified
+pub enum SampleSize {
+    N(usize),
+    Fraction(f64),
+}
+impl SampleStep {
     pub fn new(
         lazydf: LazyFrame,
-        values: Vec<String>,
-        index: Vec<String>,
-        columns: Vec<String>,
-        aggregation: String,
-        sort_columns: bool,
+        size: SampleSize,
+        replace: bool,
+        seed: Option<u64>,
+        stratify: Option<String>,
     ) -> Self {
-        PivotStep {
+        SampleStep {
             lazydf,
-            values,
-            index,
-            columns,
-            aggregation,
-            sort_columns,
+            size,
+            replace,
+            seed,
+            stratify,
         }
     }
     pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
-        let values = get_string_array(
-            step.properties
-                .get("values")
-                .unwrap()
-                .as_array()
-                .unwrap()
-                .clone(),
-        );
-        let index = get_string_array(
-            step.properties
-                .get("index")
-                .unwrap()
-                .as_array()
-                .unwrap()
-                .clone(),
-        );
-        let columns = get_string_array(
-            step.properties
-                .get("columns")
-                .unwrap()
-                .as_array()
-                .unwrap()
-                .clone(),
-        );
-        let aggregation = step
+        let size = match step.properties.get("n") {
+            Some(n) => SampleSize::N(n.as_integer().unwrap() as usize),
+            None => SampleSize::Fraction(get_number(step.properties.get("fraction").unwrap())),
+        };
+        let replace = step
             .properties
-            .get("aggregation")
-            .unwrap()
-            .as_str()
-            .unwrap()
-            .to_string();
-        let sort_columns = step
+            .get("replace")
+            .is_some_and(|r| r.as_bool().unwrap());
+        let seed = step
             .properties
-            .get("sort_columns")
-            .unwrap()
-            .as_bool()
-            .unwrap();
-        PivotStep::new(lazydf, values, index, columns, aggregation, sort_columns)
+            .get("seed")
+            .map(|s| s.as_integer().unwrap() as u64);
+        let stratify = step
+            .properties
+            .get("stratify")
+            .map(|s| s.as_str().unwrap().to_string());
+        SampleStep::new(lazydf, size, replace, seed, stratify)
     }
 }
-impl Execute for PivotStep {
+impl Execute for SampleStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let df = self.lazydf.clone().collect()?;
+        let groups: Vec<Vec<IdxSize>> = match &self.stratify {
+            Some(stratify) => group_rows(&df, std::slice::from_ref(stratify))?,
+            None => vec![(0..df.height() as IdxSize).collect()],
+        };
+        let mut rng = get_rng(self.seed);
+        let mut rows: Vec<IdxSize> = vec![];
+        for group in groups {
+            let amount = match self.size {
+                SampleSize::N(n) => n,
+                SampleSize::Fraction(fraction) => (group.len() as f64 * fraction).round() as usize,
+            };
+            if self.replace {
+                if !group.is_empty() {
+                    rows.extend((0..amount).map(|_| group[rng.gen_range(0..group.len())]));
+                }
+            } else {
+                if amount > group.len() {
+                    return Err(PolarsError::ComputeError(
+                        format!(
+                            "cannot sample {} rows out of {} without replacement",
+                            amount,
+                            group.len()
+                        )
+                        .into(),
+                    ));
+                }
+                rows.extend(
+                    rand::seq::index::sample(&mut rng, group.len(), amount)
+                        .iter()
+                        .map(|i| group[i]),
+                );
+            }
+        }
+        rows.sort_unstable();
+        let sample = df.take(&IdxCa::from_vec("rows", rows))?;
+        Ok(sample.lazy())
+    }
+    fn validate(step: &Steps) {
+        let n = step.properties.get("n");
+        let fraction = step.properties.get("fraction");
+        assert!(
+            n.is_some() != fraction.is_some(),
+            "Error! sample should contain one of the properties: n or fraction"
+        );
+        if let Some(n) = n {
+            assert!(
+                n.as_integer().is_some_and(|n| n >= 0),
+                "Error! sample n should be a non-negative integer"
+            );
+        }
+        if let Some(fraction) = fraction {
+            assert!(
+                (fraction.is_float() || fraction.is_integer()) && get_number(fraction) >= 0.0,
+                "Error! sample fraction should be a non-negative number"
+            );
+        }
+        if let Some(replace) = step.properties.get("replace") {
+            assert!(replace.is_bool(), "Error! sample replace should be true or false");
+        }
+        if let Some(seed) = step.properties.get("seed") {
+            assert!(
+                seed.as_integer().is_some_and(|s| s >= 0),
+                "Error! sample seed should be a non-negative integer"
+            );
+        }
+        if let Some(stratify) = step.properties.get("stratify") {
+            assert!(stratify.is_str(), "Error! sample stratify should be a column name");
+        }
+    }
+}
+
+/// head
+/// Keep the first n rows, or the first n rows of every group
+/// Limiting early lets Polars read only the first rows of the csv file
+pub struct HeadStep {
+    lazydf: LazyFrame,
+    n: usize,
+    by: Option<Vec<String>>,
+}
+impl HeadStep {
+    pub fn new(lazydf: LazyFrame, n: usize, by: Option<Vec<String>>) -> Self {
+        HeadStep { lazydf, n, by }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let n = step.properties.get("n").unwrap().as_integer().unwrap() as usize;
+        let by = step.properties.get("by").map(get_columns);
+        HeadStep::new(lazydf, n, by)
+    }
+}
+impl Execute for HeadStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let new_lazydf = match &self.by {
+            None => self.lazydf.clone().limit(self.n as IdxSize),
+            // the groups keep their order, and the columns are put back in their order
+            Some(by) => self
+                .lazydf
+                .clone()
+                .groupby_stable(get_array_columns(by.clone()))
+                .head(Some(self.n))
+                .select(schema_columns(&self.lazydf)?),
+        };
+        Ok(new_lazydf)
+    }
+    fn validate(step: &Steps) {
+        validate_limit(step);
+    }
+}
+
+/// tail
+/// Keep the last n rows, or the last n rows of every group
+pub struct TailStep {
+    lazydf: LazyFrame,
+    n: usize,
+    by: Option<Vec<String>>,
+}
+impl TailStep {
+    pub fn new(lazydf: LazyFrame, n: usize, by: Option<Vec<String>>) -> Self {
+        TailStep { lazydf, n, by }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let n = step.properties.get("n").unwrap().as_integer().unwrap() as usize;
+        let by = step.properties.get("by").map(get_columns);
+        TailStep::new(lazydf, n, by)
+    }
+}
+impl Execute for TailStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let new_lazydf = match &self.by {
+            None => self.lazydf.clone().tail(self.n as IdxSize),
+            // the groups keep their order, and the columns are put back in their order
+            Some(by) => self
+                .lazydf
+                .clone()
+                .groupby_stable(get_array_columns(by.clone()))
+                .tail(Some(self.n))
+                .select(schema_columns(&self.lazydf)?),
+        };
+        Ok(new_lazydf)
+    }
+    fn validate(step: &Steps) {
+        validate_limit(step);
+    }
+}
+
+// head and tail need a number of rows, and optionally the columns of the groups
+fn validate_limit(step: &Steps) {
+    assert!(
+        step.properties
+            .get("n")
+            .and_then(|n| n.as_integer())
+            .is_some_and(|n| n >= 0),
+        "Error! {} should contain a non-negative integer property: n",
+        step.operation
+    );
+    if let Some(by) = step.properties.get("by") {
+        assert!(
+            is_columns(by),
+            "Error! {} by should be a column name or a non-empty list of column names",
+            step.operation
+        );
+    }
+}
+
+/// slice
+/// Keep the rows from an offset, optionally only a number of them
+/// A negative offset counts from the end
+pub struct SliceStep {
+    lazydf: LazyFrame,
+    offset: i64,
+    length: Option<usize>,
+}
+impl SliceStep {
+    pub fn new(lazydf: LazyFrame, offset: i64, length: Option<usize>) -> Self {
+        SliceStep {
+            lazydf,
+            offset,
+            length,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let offset = step.properties.get("offset").unwrap().as_integer().unwrap();
+        let length = step
+            .properties
+            .get("length")
+            .map(|l| l.as_integer().unwrap() as usize);
+        SliceStep::new(lazydf, offset, length)
+    }
+}
+impl Execute for SliceStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let length = self.length.map_or(IdxSize::MAX, |l| l as IdxSize);
+        let new_lazydf = self.lazydf.clone().slice(self.offset, length);
+        Ok(new_lazydf)
+    }
+    fn validate(step: &Steps) {
+        assert!(
+            step.properties.get("offset").is_some_and(|o| o.is_integer()),
+            "Error! slice should contain an integer property: offset"
+        );
+        if let Some(length) = step.properties.get("length") {
+            assert!(
+                length.as_integer().is_some_and(|l| l >= 0),
+                "Error! slice length should be a non-negative integer"
+            );
+        }
+    }
+}
+
+/// window
+/// Compute a window function in a new column: rank, row number, cumulative aggregations, lag/lead, difference or percent change.
+/// The function can be computed for every group of rows, following the order of a column. The rows keep their order
+pub struct WindowStep {
+    lazydf: LazyFrame,
+    function: String,
+    col: Option<String>,
+    name: String,
+    // rows of lag, lead, diff and pct_change
+    n: i64,
+    // dense, min, max, average or ordinal ranks
+    method: String,
+    // rank the values from the largest
+    descending: bool,
+    by: Option<Vec<String>>,
+    order_by: Option<String>,
+    // order the rows from the largest value of order_by
+    order_descending: bool,
+}
+pub const WINDOW_FUNCTIONS: &[&str] = &[
+    "rank",
+    "row_number",
+    "cum_sum",
+    "cum_min",
+    "cum_max",
+    "cum_count",
+    "lag",
+    "lead",
+    "diff",
+    "pct_change",
+];
+impl WindowStep {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        lazydf: LazyFrame,
+        function: String,
+        col: Option<String>,
+        name: String,
+        n: i64,
+        method: String,
+        descending: bool,
+        by: Option<Vec<String>>,
+        order_by: Option<String>,
+        order_descending: bool,
+    ) -> Self {
+        WindowStep {
+            lazydf,
+            function,
+            col,
+            name,
+            n,
+            method,
+            descending,
+            by,
+            order_by,
+            order_descending,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let get_str = |name: &str| {
+            step.properties
+                .get(name)
+                .map(|v| v.as_str().unwrap().to_string())
+        };
+        let function = get_str("function").unwrap();
+        let col = get_str("col");
+        let name = get_str("name").unwrap_or_else(|| match &col {
+            Some(col) => format!("{}_{}", col, function),
+            None => function.clone(),
+        });
+        let n = step
+            .properties
+            .get("n")
+            .map_or(1, |n| n.as_integer().unwrap());
+        let method = get_str("method").unwrap_or_else(|| String::from("dense"));
+        let get_bool = |name: &str| {
+            step.properties
+                .get(name)
+                .is_some_and(|d| d.as_bool().unwrap())
+        };
+        let by = step.properties.get("by").map(get_columns);
+        WindowStep::new(
+            lazydf,
+            function,
+            col,
+            name,
+            n,
+            method,
+            get_bool("descending"),
+            by,
+            get_str("order_by"),
+            get_bool("order_descending"),
+        )
+    }
+
+    fn expression(&self) -> Expr {
+        let value = || col(self.col.as_deref().unwrap());
+        let shifted = || value().cast(DataType::Float64).shift(self.n);
+        match self.function.as_str() {
+            "rank" => {
+                let method = match self.method.as_str() {
+                    "min" => RankMethod::Min,
+                    "max" => RankMethod::Max,
+                    "average" => RankMethod::Average,
+                    "ordinal" => RankMethod::Ordinal,
+                    _ => RankMethod::Dense,
+                };
+                value().rank(RankOptions {
+                    method,
+                    descending: self.descending,
+                })
+            }
+            "row_number" => col("__row").cumcount(false) + lit(1 as IdxSize),
+            "cum_sum" => value().cumsum(false),
+            "cum_min" => value().cummin(false),
+            "cum_max" => value().cummax(false),
+            "cum_count" => value().is_not_null().cast(DataType::UInt32).cumsum(false),
+            "lag" => value().shift(self.n),
+            "lead" => value().shift(-self.n),
+            "diff" => value().cast(DataType::Float64) - shifted(),
+            "pct_change" => (value().cast(DataType::Float64) - shifted()) / shifted(),
+            function => panic!("Error! {} is not a valid window function", function),
+        }
+    }
+}
+impl Execute for WindowStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let mut expr = self.expression();
+        if let Some(by) = &self.by {
+            expr = expr.over(get_array_columns(by.clone()));
+        }
+        // the row count numbers the rows, and keeps their order when they are sorted to compute the function
+        let mut new_lazydf = self.lazydf.clone().with_row_count("__row", None);
+        if let Some(order_by) = &self.order_by {
+            new_lazydf = new_lazydf.sort(
+                order_by,
+                SortOptions {
+                    descending: self.order_descending,
+                    nulls_last: true,
+                },
+            );
+        }
+        new_lazydf = new_lazydf.with_column(expr.alias(&self.name));
+        if self.order_by.is_some() {
+            new_lazydf = new_lazydf.sort("__row", SortOptions::default());
+        }
+        Ok(new_lazydf.drop_columns(["__row"]))
+    }
+    fn validate(step: &Steps) {
+        let function = step.properties.get("function").and_then(|f| f.as_str());
+        assert!(
+            function.is_some_and(|f| WINDOW_FUNCTIONS.contains(&f)),
+            "Error! window function should be one of {:?}",
+            WINDOW_FUNCTIONS
+        );
+        if function != Some("row_number") {
+            assert!(
+                step.properties.get("col").is_some_and(|c| c.is_str()),
+                "Error! window does not contain property: col"
+            );
+        }
+        for name in ["name", "order_by"] {
+            if let Some(value) = step.properties.get(name) {
+                assert!(value.is_str(), "Error! window {} should be a string", name);
+            }
+        }
+        if let Some(n) = step.properties.get("n") {
+            assert!(
+                n.as_integer().is_some_and(|n| n > 0),
+                "Error! window n should be a positive integer"
+            );
+        }
+        if let Some(method) = step.properties.get("method") {
+            assert!(
+                method
+                    .as_str()
+                    .is_some_and(|m| ["dense", "min", "max", "average", "ordinal"].contains(&m)),
+                "Error! window method should be dense, min, max, average or ordinal"
+            );
+        }
+        for name in ["descending", "order_descending"] {
+            if let Some(value) = step.properties.get(name) {
+                assert!(
+                    value.is_bool(),
+                    "Error! window {} should be true or false",
+                    name
+                );
+            }
+        }
+        if let Some(by) = step.properties.get("by") {
+            assert!(
+                is_columns(by),
+                "Error! window by should be a column name or a non-empty list of column names"
+            );
+        }
+    }
+}
+
+/// rolling
+/// Compute a rolling aggregation in a new column: mean, sum, min, max, std or median.
+/// The window is a number of rows, or a duration (e.g. "5m") over a date or datetime column given by on.
+/// The aggregation can be computed for every group of rows. The rows keep their order
+pub struct RollingStep {
+    lazydf: LazyFrame,
+    function: String,
+    col: String,
+    name: String,
+    window: RollingWindow,
+    // values in the window needed to compute a result, otherwise it is null
+    min_periods: usize,
+    center: bool,
+    by: Option<Vec<String>>,
+}
+/// Size of a rolling window
+pub enum RollingWindow {
+    Rows(usize),
+    // duration over a date/datetime column, with the format used to parse it when it is a string
+    Time {
+        duration: String,
+        on: String,
+        format: Option<String>,
+    },
+}
+pub const ROLLING_FUNCTIONS: &[&str] = &["mean", "sum", "min", "max", "std", "median"];
+impl RollingStep {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        lazydf: LazyFrame,
+        function: String,
+        col: String,
+        name: String,
+        window: RollingWindow,
+        min_periods: usize,
+        center: bool,
+        by: Option<Vec<String>>,
+    ) -> Self {
+        RollingStep {
+            lazydf,
+            function,
+            col,
+            name,
+            window,
+            min_periods,
+            center,
+            by,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let get_str = |name: &str| {
+            step.properties
+                .get(name)
+                .map(|v| v.as_str().unwrap().to_string())
+        };
+        let function = get_str("function").unwrap();
+        let col = get_str("col").unwrap();
+        let name = get_str("name").unwrap_or_else(|| format!("{}_rolling_{}", col, function));
+        let window = step.properties.get("window").unwrap();
+        let window = match window.as_integer() {
+            Some(rows) => RollingWindow::Rows(rows as usize),
+            None => RollingWindow::Time {
+                duration: window.as_str().unwrap().to_string(),
+                on: get_str("on").unwrap(),
+                format: get_str("format"),
+            },
+        };
+        let min_periods = step
+            .properties
+            .get("min_periods")
+            .map_or(1, |m| m.as_integer().unwrap() as usize);
+        let center = step
+            .properties
+            .get("center")
+            .is_some_and(|c| c.as_bool().unwrap());
+        let by = step.properties.get("by").map(get_columns);
+        RollingStep::new(lazydf, function, col, name, window, min_periods, center, by)
+    }
+}
+impl Execute for RollingStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        // the row count keeps the order of the rows when they are sorted by time
+        let mut new_lazydf = self.lazydf.clone().with_row_count("__row", None);
+        let options = match &self.window {
+            RollingWindow::Rows(rows) => RollingOptions {
+                window_size: Duration::new(*rows as i64),
+                min_periods: self.min_periods,
+                center: self.center,
+                ..Default::default()
+            },
+            RollingWindow::Time {
+                duration,
+                on,
+                format,
+            } => {
+                // time windows need a sorted datetime column without nulls
+                let time = datetime_column(&self.lazydf, on, format.clone())?;
+                new_lazydf = new_lazydf
+                    .with_column(time.alias("__on"))
+                    .sort("__on", SortOptions::default());
+                RollingOptions {
+                    window_size: Duration::parse(duration),
+                    min_periods: self.min_periods,
+                    center: self.center,
+                    by: Some(String::from("__on")),
+                    closed_window: Some(ClosedWindow::Right),
+                    ..Default::default()
+                }
+            }
+        };
+        let value = col(&self.col).cast(DataType::Float64);
+        let mut expr = match self.function.as_str() {
+            "mean" => value.rolling_mean(options),
+            "sum" => value.rolling_sum(options),
+            "min" => value.rolling_min(options),
+            "max" => value.rolling_max(options),
+            "std" => value.rolling_std(options),
+            "median" => value.rolling_median(options),
+            function => panic!("Error! {} is not a valid rolling function", function),
+        };
+        if let Some(by) = &self.by {
+            expr = expr.over(get_array_columns(by.clone()));
+        }
+        new_lazydf = new_lazydf.with_column(expr.alias(&self.name));
+        if let RollingWindow::Time { .. } = self.window {
+            new_lazydf = new_lazydf
+                .sort("__row", SortOptions::default())
+                .drop_columns(["__on"]);
+        }
+        Ok(new_lazydf.drop_columns(["__row"]))
+    }
+    fn validate(step: &Steps) {
+        assert!(
+            step.properties
+                .get("function")
+                .and_then(|f| f.as_str())
+                .is_some_and(|f| ROLLING_FUNCTIONS.contains(&f)),
+            "Error! rolling function should be one of {:?}",
+            ROLLING_FUNCTIONS
+        );
+        assert!(
+            step.properties.get("col").is_some_and(|c| c.is_str()),
+            "Error! rolling does not contain property: col"
+        );
+        match step.properties.get("window") {
+            Some(Value::Integer(rows)) => assert!(
+                *rows > 0,
+                "Error! rolling window should be a positive number of rows"
+            ),
+            Some(Value::String(duration)) => {
+                assert!(
+                    is_duration(duration),
+                    "Error! rolling window {} is not a valid duration, e.g. 30s, 5m, 1h or 2d",
+                    duration
+                );
+                assert!(
+                    step.properties.get("on").is_some_and(|o| o.is_str()),
+                    "Error! rolling over a duration should contain property: on"
+                );
+            }
+            _ => panic!("Error! rolling should contain property: window (rows or a duration)"),
+        }
+        for name in ["name", "on", "format"] {
+            if let Some(value) = step.properties.get(name) {
+                assert!(value.is_str(), "Error! rolling {} should be a string", name);
+            }
+        }
+        if let Some(min_periods) = step.properties.get("min_periods") {
+            assert!(
+                min_periods.as_integer().is_some_and(|m| m > 0),
+                "Error! rolling min_periods should be a positive integer"
+            );
+        }
+        if let Some(center) = step.properties.get("center") {
+            assert!(
+                center.is_bool(),
+                "Error! rolling center should be true or false"
+            );
+        }
+        if let Some(by) = step.properties.get("by") {
+            assert!(
+                is_columns(by),
+                "Error! rolling by should be a column name or a non-empty list of column names"
+            );
+        }
+    }
+}
+
+/// resample
+/// Group a date or datetime column in intervals of fixed duration (e.g. 1s, 5m, 1d), for every group of rows,
+/// and aggregate the values of every interval. Each interval is labeled with its start.
+/// Optionally the intervals without rows are added, filling their values forward or interpolating them
+pub struct ResampleStep {
+    lazydf: LazyFrame,
+    on: String,
+    format: Option<String>,
+    every: String,
+    by: Vec<String>,
+    // columns and their aggregations. Columns with a list of aggregations are named {col}_{aggregation}
+    aggregations: Vec<(String, Vec<String>, bool)>,
+    // none, null, forward or interpolate
+    fill: String,
+}
+pub const RESAMPLE_AGGREGATIONS: &[&str] = &[
+    "first", "last", "max", "mean", "median", "min", "sum", "count", "std",
+];
+pub const RESAMPLE_FILLS: &[&str] = &["none", "null", "forward", "interpolate"];
+impl ResampleStep {
+    pub fn new(
+        lazydf: LazyFrame,
+        on: String,
+        format: Option<String>,
+        every: String,
+        by: Vec<String>,
+        aggregations: Vec<(String, Vec<String>, bool)>,
+        fill: String,
+    ) -> Self {
+        ResampleStep {
+            lazydf,
+            on,
+            format,
+            every,
+            by,
+            aggregations,
+            fill,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let get_str = |name: &str| {
+            step.properties
+                .get(name)
+                .map(|v| v.as_str().unwrap().to_string())
+        };
+        let aggregations = step
+            .properties
+            .get("aggregations")
+            .unwrap()
+            .as_table()
+            .unwrap()
+            .iter()
+            .map(|(col, functions)| (col.clone(), get_columns(functions), functions.is_array()))
+            .collect();
+        let by = step.properties.get("by").map_or(vec![], get_columns);
+        ResampleStep::new(
+            lazydf,
+            get_str("on").unwrap(),
+            get_str("format"),
+            get_str("every").unwrap(),
+            by,
+            aggregations,
+            get_str("fill").unwrap_or_else(|| String::from("none")),
+        )
+    }
+
+    fn aggregation(&self, col_name: &str, function: &str) -> Expr {
+        let value = col(col_name);
+        match function {
+            "first" => value.first(),
+            "last" => value.last(),
+            "max" => value.max(),
+            "mean" => value.mean(),
+            "median" => value.median(),
+            "min" => value.min(),
+            "sum" => value.sum(),
+            "count" => value.count(),
+            "std" => value.std(1),
+            function => panic!("Error! {} is not a valid resample aggregation", function),
+        }
+    }
+
+    /// Add the intervals without rows, between the first and last interval of every group
+    /// The counts of the added intervals are 0, the fill only applies to the other aggregations
+    fn upsample(
+        &self,
+        lazydf: LazyFrame,
+        names: &[(String, bool)],
+    ) -> Result<LazyFrame, PolarsError> {
+        let df = lazydf.collect()?.upsample_stable(
+            self.by.clone(),
+            &self.on,
+            Duration::parse(&self.every),
+            Duration::parse("0ns"),
+        )?;
+        // the added rows only carry their time, every group starts with a row of the data
+        let keys: Vec<Expr> = self.by.iter().map(|b| col(b).forward_fill(None)).collect();
+        let values: Vec<Expr> = names
+            .iter()
+            .map(|(name, count)| {
+                let value = col(name);
+                let value = match self.fill.as_str() {
+                    _ if *count => value.fill_null(lit(0 as IdxSize)),
+                    "forward" => value.forward_fill(None),
+                    "interpolate" => value.cast(DataType::Float64).interpolate(),
+                    _ => value,
+                };
+                match self.by.is_empty() {
+                    true => value,
+                    false => value.over(get_array_columns(self.by.clone())),
+                }
+            })
+            .collect();
+        Ok(df.lazy().with_columns(keys).with_columns(values))
+    }
+}
+impl Execute for ResampleStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let time = datetime_column(&self.lazydf, &self.on, self.format.clone())?;
+        let every = Duration::parse(&self.every);
+        let options = DynamicGroupOptions {
+            index_column: self.on.clone(),
+            every,
+            period: every,
+            offset: Duration::parse("0ns"),
+            truncate: true,
+            include_boundaries: false,
+            closed_window: ClosedWindow::Left,
+        };
+        let mut names = vec![];
+        let mut exprs = vec![];
+        for (col_name, functions, named) in &self.aggregations {
+            for function in functions {
+                let name = match named {
+                    true => format!("{}_{}", col_name, function),
+                    false => col_name.clone(),
+                };
+                exprs.push(self.aggregation(col_name, function).alias(&name));
+                names.push((name, function == "count"));
+            }
+        }
+        // the intervals are found in the time column sorted within every group
+        let mut sort_by = get_array_columns(self.by.clone());
+        sort_by.push(col(&self.on));
+        let mut columns = get_array_columns(self.by.clone());
+        columns.push(col(&self.on));
+        columns.extend(names.iter().map(|(n, _)| col(n)));
+        let mut new_lazydf = self
+            .lazydf
+            .clone()
+            .with_column(time.alias(&self.on))
+            .sort_by_exprs(sort_by, vec![false; self.by.len() + 1], false)
+            .groupby_dynamic(get_array_columns(self.by.clone()), options)
+            .agg(exprs);
+        if self.fill != "none" {
+            new_lazydf = self.upsample(new_lazydf, &names)?;
+        }
+        Ok(new_lazydf.select(columns))
+    }
+    fn validate(step: &Steps) {
+        assert!(
+            step.properties.get("on").is_some_and(|o| o.is_str()),
+            "Error! resample does not contain property: on"
+        );
+        assert!(
+            step.properties
+                .get("every")
+                .and_then(|e| e.as_str())
+                .is_some_and(is_duration),
+            "Error! resample every should be a duration, e.g. 30s, 5m, 1h or 2d"
+        );
+        let aggregations = step
+            .properties
+            .get("aggregations")
+            .and_then(|a| a.as_table());
+        assert!(
+            aggregations.is_some_and(|a| !a.is_empty()),
+            "Error! resample should contain property: aggregations (a table of columns and aggregations)"
+        );
+        for (col_name, functions) in aggregations.unwrap() {
+            assert!(
+                is_columns(functions)
+                    && get_columns(functions)
+                        .iter()
+                        .all(|f| RESAMPLE_AGGREGATIONS.contains(&f.as_str())),
+                "Error! resample aggregation of {} should be one or a list of {:?}",
+                col_name,
+                RESAMPLE_AGGREGATIONS
+            );
+        }
+        if let Some(format) = step.properties.get("format") {
+            assert!(format.is_str(), "Error! resample format should be a string");
+        }
+        if let Some(by) = step.properties.get("by") {
+            assert!(
+                is_columns(by),
+                "Error! resample by should be a column name or a non-empty list of column names"
+            );
+        }
+        if let Some(fill) = step.properties.get("fill") {
+            assert!(
+                fill.as_str().is_some_and(|f| RESAMPLE_FILLS.contains(&f)),
+                "Error! resample fill should be one of {:?}",
+                RESAMPLE_FILLS
+            );
+        }
+    }
+}
+
+/// bin
+/// Cut a numeric column in intervals, writing the interval of every value in a new column.
+/// The breaks between the intervals are given, or found from the data as equal-width intervals or quantiles
+pub struct BinStep {
+    lazydf: LazyFrame,
+    col: String,
+    name: String,
+    breaks: BinBreaks,
+    labels: Option<Vec<String>>,
+    // the intervals are closed on the right, (a, b], or on the left, [a, b)
+    right: bool,
+    categorical: bool,
+    // decimals of the breaks in the labels
+    precision: usize,
+}
+/// Breaks of the intervals of a bin step
+pub enum BinBreaks {
+    Breaks(Vec<f64>),
+    // number of intervals of the same width between the minimum and maximum
+    Width(usize),
+    // probabilities of the quantiles used as breaks
+    Quantiles(Vec<f64>),
+}
+impl BinStep {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        lazydf: LazyFrame,
+        col: String,
+        name: String,
+        breaks: BinBreaks,
+        labels: Option<Vec<String>>,
+        right: bool,
+        categorical: bool,
+        precision: usize,
+    ) -> Self {
+        BinStep {
+            lazydf,
+            col,
+            name,
+            breaks,
+            labels,
+            right,
+            categorical,
+            precision,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let get_str = |name: &str| {
+            step.properties
+                .get(name)
+                .map(|v| v.as_str().unwrap().to_string())
+        };
+        let col = get_str("col").unwrap();
+        let name = get_str("name").unwrap_or_else(|| format!("{}_bin", col));
+        let get_numbers = |value: &Value| -> Vec<f64> {
+            value.as_array().unwrap().iter().map(get_number).collect()
+        };
+        let breaks = match (
+            step.properties.get("breaks"),
+            step.properties.get("bins"),
+            step.properties.get("quantiles"),
+        ) {
+            (Some(breaks), _, _) => BinBreaks::Breaks(get_numbers(breaks)),
+            (_, Some(bins), _) => BinBreaks::Width(bins.as_integer().unwrap() as usize),
+            (_, _, Some(quantiles)) => match quantiles.as_integer() {
+                Some(n) => BinBreaks::Quantiles((0..=n).map(|i| i as f64 / n as f64).collect()),
+                None => BinBreaks::Quantiles(get_numbers(quantiles)),
+            },
+            _ => {
+                panic!("Error! bin should contain one of the properties: breaks, bins or quantiles")
+            }
+        };
+        let labels = step
+            .properties
+            .get("labels")
+            .map(|l| get_string_array(l.as_array().unwrap().clone()));
+        let right = get_str("closed").is_none_or(|c| c == "right");
+        let categorical = get_str("dtype").is_some_and(|d| d == "categorical");
+        let precision = step
+            .properties
+            .get("precision")
+            .map_or(3, |p| p.as_integer().unwrap() as usize);
+        BinStep::new(
+            lazydf,
+            col,
+            name,
+            breaks,
+            labels,
+            right,
+            categorical,
+            precision,
+        )
+    }
+
+    /// Breaks of the intervals, in increasing order
+    /// Equal-width intervals and quantiles are found from the values of the column
+    fn find_breaks(&self) -> Result<Vec<f64>, PolarsError> {
+        let value = || col(&self.col).cast(DataType::Float64);
+        let stats: Vec<Expr> = match &self.breaks {
+            BinBreaks::Breaks(breaks) => return Ok(breaks.clone()),
+            BinBreaks::Width(_) => vec![value().min().alias("min"), value().max().alias("max")],
+            BinBreaks::Quantiles(probabilities) => probabilities
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    value()
+                        .quantile(*p, QuantileInterpolOptions::Linear)
+                        .alias(&i.to_string())
+                })
+                .collect(),
+        };
+        let stats = self.lazydf.clone().select(stats).collect()?;
+        let stats: Vec<f64> = stats
+            .get_columns()
+            .iter()
+            .map(|s| {
+                s.cast(&DataType::Float64)?.f64()?.get(0).ok_or_else(|| {
+                    PolarsError::ComputeError(
+                        format!("Cannot find the bins of {}, it has no values", self.col).into(),
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let mut breaks = match self.breaks {
+            BinBreaks::Width(n) => {
+                let width = (stats[1] - stats[0]) / n as f64;
+                let mut breaks: Vec<f64> = (0..n).map(|i| stats[0] + width * i as f64).collect();
+                // the last break is the maximum itself, the rounded sum can fall below it
+                breaks.push(stats[1]);
+                breaks
+            }
+            _ => stats,
+        };
+        // equal quantiles are a single break, and a column with a single value is a single interval with it
+        breaks.dedup();
+        if breaks.len() == 1 {
+            breaks.push(breaks[0]);
+        }
+        Ok(breaks)
+    }
+
+    fn format_break(&self, value: f64) -> String {
+        let text = format!("{:.*}", self.precision, value);
+        match text.contains('.') {
+            true => text.trim_end_matches('0').trim_end_matches('.').to_string(),
+            false => text,
+        }
+    }
+}
+impl Execute for BinStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let breaks = self.find_breaks()?;
+        let intervals = breaks.len().saturating_sub(1);
+        let labels = match &self.labels {
+            Some(labels) if labels.len() != intervals => {
+                return Err(PolarsError::ComputeError(
+                    format!(
+                        "bin of {} has {} labels, but some of the breaks found in the data are equal and leave {} intervals",
+                        self.col,
+                        labels.len(),
+                        intervals
+                    )
+                    .into(),
+                ))
+            }
+            Some(labels) => labels.clone(),
+            None => (0..intervals)
+                .map(|i| {
+                    // the outer break of the first (or last) interval is included
+                    let (open, close) = match (self.right, i) {
+                        (true, 0) => ("[", "]"),
+                        (true, _) => ("(", "]"),
+                        (false, i) if i + 1 == intervals => ("[", "]"),
+                        (false, _) => ("[", ")"),
+                    };
+                    format!(
+                        "{}{}, {}{}",
+                        open,
+                        self.format_break(breaks[i]),
+                        self.format_break(breaks[i + 1]),
+                        close
+                    )
+                })
+                .collect(),
+        };
+        let value = || col(&self.col).cast(DataType::Float64);
+        // values out of the breaks are null
+        let mut expr = lit(NULL).cast(DataType::Utf8);
+        for i in (0..intervals).rev() {
+            let (lower, upper) = (lit(breaks[i]), lit(breaks[i + 1]));
+            let inside = match (self.right, i) {
+                (true, 0) => value().gt_eq(lower).and(value().lt_eq(upper)),
+                (true, _) => value().gt(lower).and(value().lt_eq(upper)),
+                (false, i) if i + 1 == intervals => value().gt_eq(lower).and(value().lt_eq(upper)),
+                (false, _) => value().gt_eq(lower).and(value().lt(upper)),
+            };
+            expr = when(inside).then(lit(labels[i].as_str())).otherwise(expr);
+        }
+        if self.categorical {
+            expr = expr.cast(DataType::Categorical(None));
+        }
+        let new_lazydf = self.lazydf.clone().with_column(expr.alias(&self.name));
+        Ok(new_lazydf)
+    }
+    fn validate(step: &Steps) {
+        assert!(
+            step.properties.get("col").is_some_and(|c| c.is_str()),
+            "Error! bin does not contain property: col"
+        );
+        let is_number = |v: &Value| v.is_integer() || v.is_float();
+        let is_numbers = |v: &Value| {
+            v.as_array()
+                .is_some_and(|a| a.len() > 1 && a.iter().all(is_number))
+        };
+        let is_increasing = |v: &Value| {
+            let numbers: Vec<f64> = v.as_array().unwrap().iter().map(get_number).collect();
+            numbers.windows(2).all(|w| w[0] < w[1])
+        };
+        let kinds = ["breaks", "bins", "quantiles"]
+            .iter()
+            .filter(|k| step.properties.contains_key(**k))
+            .count();
+        assert!(
+            kinds == 1,
+            "Error! bin should contain one (and only one) of the properties: breaks, bins or quantiles"
+        );
+        let intervals = if let Some(breaks) = step.properties.get("breaks") {
+            assert!(
+                is_numbers(breaks) && is_increasing(breaks),
+                "Error! bin breaks should be a list of at least two increasing numbers"
+            );
+            breaks.as_array().unwrap().len() - 1
+        } else if let Some(bins) = step.properties.get("bins") {
+            assert!(
+                bins.as_integer().is_some_and(|b| b > 0),
+                "Error! bin bins should be a positive integer"
+            );
+            bins.as_integer().unwrap() as usize
+        } else {
+            let quantiles = step.properties.get("quantiles").unwrap();
+            match quantiles.as_integer() {
+                Some(n) => {
+                    assert!(n > 0, "Error! bin quantiles should be a positive integer");
+                    n as usize
+                }
+                None => {
+                    assert!(
+                        is_numbers(quantiles)
+                            && is_increasing(quantiles)
+                            && quantiles
+                                .as_array()
+                                .unwrap()
+                                .iter()
+                                .all(|q| (0.0..=1.0).contains(&get_number(q))),
+                        "Error! bin quantiles should be a number of quantiles or a list of increasing probabilities between 0 and 1"
+                    );
+                    quantiles.as_array().unwrap().len() - 1
+                }
+            }
+        };
+        if let Some(labels) = step.properties.get("labels") {
+            assert!(
+                labels
+                    .as_array()
+                    .is_some_and(|l| l.len() == intervals && l.iter().all(|v| v.is_str())),
+                "Error! bin labels should be a list of {} strings, one for every interval",
+                intervals
+            );
+        }
+        for name in ["name", "closed", "dtype"] {
+            if let Some(value) = step.properties.get(name) {
+                assert!(value.is_str(), "Error! bin {} should be a string", name);
+            }
+        }
+        if let Some(closed) = step.properties.get("closed") {
+            assert!(
+                closed.as_str().is_some_and(|c| c == "right" || c == "left"),
+                "Error! bin closed should be right or left"
+            );
+        }
+        if let Some(dtype) = step.properties.get("dtype") {
+            assert!(
+                dtype
+                    .as_str()
+                    .is_some_and(|d| d == "string" || d == "categorical"),
+                "Error! bin dtype should be string or categorical"
+            );
+        }
+        if let Some(precision) = step.properties.get("precision") {
+            assert!(
+                precision.as_integer().is_some_and(|p| p >= 0),
+                "Error! bin precision should be a non-negative integer"
+            );
+        }
+    }
+}
+
+/// encode
+/// Encode categorical columns as numbers for models: one-hot (a 0/1 column for every category),
+/// ordinal (the position of the category in an order) or frequency (the proportion of rows of the category).
+/// The categories and their codes can be saved in a file and reused in a later run, so different data encode identically
+pub struct EncodeStep {
+    lazydf: LazyFrame,
+    cols: Vec<String>,
+    method: String,
+    // one-hot columns are named with the prefix followed by the category, {col}_ by default
+    prefix: Option<String>,
+    drop_first: bool,
+    order: Option<Vec<String>>,
+    mapping: Option<String>,
+    load_mapping: Option<String>,
+}
+pub const ENCODE_METHODS: &[&str] = &["onehot", "ordinal", "frequency"];
+/// Categories of a column and their codes
+type Codes = Vec<(String, f64)>;
+impl EncodeStep {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        lazydf: LazyFrame,
+        cols: Vec<String>,
+        method: String,
+        prefix: Option<String>,
+        drop_first: bool,
+        order: Option<Vec<String>>,
+        mapping: Option<String>,
+        load_mapping: Option<String>,
+    ) -> Self {
+        EncodeStep {
+            lazydf,
+            cols,
+            method,
+            prefix,
+            drop_first,
+            order,
+            mapping,
+            load_mapping,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let get_str = |name: &str| {
+            step.properties
+                .get(name)
+                .map(|v| v.as_str().unwrap().to_string())
+        };
+        let cols = get_columns(step.properties.get("col").unwrap());
+        let drop_first = step
+            .properties
+            .get("drop_first")
+            .is_some_and(|d| d.as_bool().unwrap());
+        let order = step
+            .properties
+            .get("order")
+            .map(|o| get_string_array(o.as_array().unwrap().clone()));
+        EncodeStep::new(
+            lazydf,
+            cols,
+            get_str("method").unwrap(),
+            get_str("prefix"),
+            drop_first,
+            order,
+            get_str("mapping"),
+            get_str("load_mapping"),
+        )
+    }
+
+    /// Categories of every column and their codes
+    /// Without an order or a mapping file they are the distinct values of the column, so the previous steps are computed once more
+    fn find_codes(&self) -> Result<Vec<(String, Codes)>, PolarsError> {
+        let dtypes = Schema::from(
+            [
+                Field::new("column", DataType::Utf8),
+                Field::new("category", DataType::Utf8),
+                Field::new("code", DataType::Float64),
+            ]
+            .into_iter(),
+        );
+        let loaded = match &self.load_mapping {
+            Some(path) => Some(
+                CsvReader::from_path(path)
+                    .unwrap_or_else(|e| {
+                        panic!("Error! Cannot read the mapping file {}: {}", path, e)
+                    })
+                    .has_header(true)
+                    .with_dtypes(Some(&dtypes))
+                    .finish()?,
+            ),
+            None => None,
+        };
+        let mut codes = vec![];
+        for c in &self.cols {
+            let value = col(c).cast(DataType::Utf8);
+            let categories: DataFrame = match (&loaded, &self.order) {
+                (Some(loaded), _) => loaded
+                    .clone()
+                    .lazy()
+                    .filter(col("column").eq(lit(c.as_str())))
+                    .select([col("category"), col("code")])
+                    .collect()?,
+                (None, Some(order)) => df!(
+                    "category" => order,
+                    "code" => (0..order.len()).map(|i| i as f64).collect::<Vec<f64>>(),
+                )?,
+                (None, None) if self.method == "frequency" => self
+                    .lazydf
+                    .clone()
+                    .select([value.alias("category")])
+                    .groupby([col("category")])
+                    .agg([count().alias("code")])
+                    .filter(col("category").is_not_null())
+                    .with_column(col("code").cast(DataType::Float64) / col("code").sum())
+                    .sort("category", SortOptions::default())
+                    .collect()?,
+                (None, None) => self
+                    .lazydf
+                    .clone()
+                    .select([value.alias("category")])
+                    .unique(None, UniqueKeepStrategy::First)
+                    .filter(col("category").is_not_null())
+                    .sort("category", SortOptions::default())
+                    .with_row_count("code", None)
+                    .with_column(col("code").cast(DataType::Float64))
+                    .collect()?,
+            };
+            assert!(
+                categories.height() > 0,
+                "Error! encode found no categories of {}",
+                c
+            );
+            let category = categories.column("category")?.utf8()?;
+            let code = categories.column("code")?.f64()?;
+            let pairs = category
+                .into_iter()
+                .zip(code)
+                .map(|(category, code)| (category.unwrap_or("").to_string(), code.unwrap_or(0.0)))
+                .collect();
+            codes.push((c.clone(), pairs));
+        }
+        Ok(codes)
+    }
+
+    /// Save the categories of every column and their codes
+    fn save_mapping(&self, path: &str, codes: &[(String, Codes)]) -> Result<(), PolarsError> {
+        let mut mapping = DataFrame::default();
+        for (c, pairs) in codes {
+            let df = df!(
+                "column" => vec![c.as_str(); pairs.len()],
+                "category" => pairs.iter().map(|(category, _)| category.as_str()).collect::<Vec<&str>>(),
+                "code" => pairs.iter().map(|(_, code)| *code).collect::<Vec<f64>>(),
+            )?;
+            mapping.vstack_mut(&df)?;
+        }
+        let mut file = fs::File::create(path)
+            .unwrap_or_else(|e| panic!("Error! Cannot create the mapping file {}: {}", path, e));
+        CsvWriter::new(&mut file).finish(&mut mapping)
+    }
+
+    /// Expressions of the encoded column
+    /// Categories that are not in the mapping are null, or 0 in every one-hot column
+    fn encode(&self, c: &str, pairs: &[(String, f64)]) -> Vec<Expr> {
+        let value = || col(c).cast(DataType::Utf8);
+        match self.method.as_str() {
+            "onehot" => {
+                let prefix = self.prefix.clone().unwrap_or_else(|| format!("{}_", c));
+                let skip = usize::from(self.drop_first);
+                pairs
+                    .iter()
+                    .skip(skip)
+                    .map(|(category, _)| {
+                        value()
+                            .eq(lit(category.as_str()))
+                            .fill_null(lit(false))
+                            .cast(DataType::Int32)
+                            .alias(&format!("{}{}", prefix, category))
+                    })
+                    .collect()
+            }
+            method => {
+                let codes: HashMap<String, f64> = pairs.iter().cloned().collect();
+                let dtype = match method {
+                    "ordinal" => DataType::Int64,
+                    _ => DataType::Float64,
+                };
+                let expr = value()
+                    .map(
+                        move |s| {
+                            let encoded: Float64Chunked = s
+                                .utf8()?
+                                .into_iter()
+                                .map(|v| v.and_then(|v| codes.get(v).copied()))
+                                .collect();
+                            Ok(encoded.into_series())
+                        },
+                        GetOutput::from_type(DataType::Float64),
+                    )
+                    .cast(dtype)
+                    .alias(c);
+                vec![expr]
+            }
+        }
+    }
+}
+impl Execute for EncodeStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let codes = self.find_codes()?;
+        if let Some(path) = &self.mapping {
+            self.save_mapping(path, &codes)?;
+        }
+        // the encoded columns take the place of the original columns
+        let columns: Vec<Expr> = self
+            .lazydf
+            .schema()?
+            .iter_names()
+            .flat_map(|name| match codes.iter().find(|(c, _)| c == name) {
+                Some((c, pairs)) => self.encode(c, pairs),
+                None => vec![col(name)],
+            })
+            .collect();
+        let new_lazydf = self.lazydf.clone().select(columns);
+        Ok(new_lazydf)
+    }
+    fn validate(step: &Steps) {
+        assert!(
+            step.properties.get("col").is_some_and(is_columns),
+            "Error! encode should contain property: col (a column name or a list of column names)"
+        );
+        let method = step.properties.get("method").and_then(|m| m.as_str());
+        assert!(
+            method.is_some_and(|m| ENCODE_METHODS.contains(&m)),
+            "Error! encode method should be one of {:?}",
+            ENCODE_METHODS
+        );
+        for name in ["prefix", "mapping", "load_mapping"] {
+            if let Some(value) = step.properties.get(name) {
+                assert!(value.is_str(), "Error! encode {} should be a string", name);
+            }
+        }
+        if let Some(drop_first) = step.properties.get("drop_first") {
+            assert!(
+                drop_first.is_bool(),
+                "Error! encode drop_first should be true or false"
+            );
+        }
+        if let Some(order) = step.properties.get("order") {
+            assert!(
+                order
+                    .as_array()
+                    .is_some_and(|o| !o.is_empty() && o.iter().all(|v| v.is_str())),
+                "Error! encode order should be a non-empty list of strings"
+            );
+            assert!(
+                method == Some("ordinal"),
+                "Error! encode order is only used by the ordinal method"
+            );
+            assert!(
+                !step.properties.contains_key("load_mapping"),
+                "Error! encode should contain either order or load_mapping, not both"
+            );
+        }
+    }
+}
+
+/// filter_contains
+/// filter a string column based on a regex expression
+pub struct FilterContainsStep {
+    lazydf: LazyFrame,
+    col: String,
+    value: String,
+}
+impl FilterContainsStep {
+    pub fn new(lazydf: LazyFrame, col: String, value: String) -> Self {
+        FilterContainsStep { lazydf, col, value }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let col = step
+            .properties
+            .get("col")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string();
+        let value = step
+            .properties
+            .get("value")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string();
+        FilterContainsStep::new(lazydf, col, value)
+    }
+}
+impl Execute for FilterContainsStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let expr: Expr = col(self.col.as_str()).str().contains(self.value.clone());
+        let new_lazydf = self.lazydf.clone().filter(expr);
+        Ok(new_lazydf)
+    }
+    fn validate(step: &Steps) {
+        assert!(
+            step.properties.contains_key("col"),
+            "Error filter_contains does not contain property: col"
+        );
+        assert!(step.properties.contains_key("value"));
+    }
+}
+
+///remove_na
+/// Remove rows that are NA in a specific column
+pub struct RemoveNAStep {
+    lazydf: LazyFrame,
+    col: String,
+}
+impl RemoveNAStep {
+    pub fn new(lazydf: LazyFrame, col: String) -> Self {
+        RemoveNAStep { lazydf, col }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let col = step
+            .properties
+            .get("col")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string();
+        RemoveNAStep::new(lazydf, col)
+    }
+}
+impl Execute for RemoveNAStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let expr = col(self.col.as_str());
+        let new_lazydf = self.lazydf.clone().filter(expr.is_not_null());
+        Ok(new_lazydf)
+    }
+    fn validate(step: &Steps) {
+        assert!(
+            step.properties.contains_key("col"),
+            "Error! remove_na does not contain property: col"
+        );
+    }
+}
+
+/// recode
+/// recode items of a column, with the values given as lists or read from a lookup CSV file.
+/// Values that are not recoded are kept, made null, set to a default value or make the step fail.
+/// The column is joined with the values and their new values, so the recode stays in the query plan
+pub struct RecodeStep {
+    lazydf: LazyFrame,
+    col: String,
+    mapping: RecodeMapping,
+    // keep, null, default or error
+    unmatched: String,
+    default: Option<Value>,
+}
+/// Values replaced by a recode step and their new values
+pub enum RecodeMapping {
+    Lists { from: Vec<Value>, to: Vec<Value> },
+    // CSV file with the values in the first column and the new values in the second one
+    Lookup(String),
+}
+pub const RECODE_UNMATCHED: &[&str] = &["keep", "null", "default", "error"];
+impl RecodeStep {
+    pub fn new(
+        lazydf: LazyFrame,
+        col: String,
+        mapping: RecodeMapping,
+        unmatched: String,
+        default: Option<Value>,
+    ) -> Self {
+        RecodeStep {
+            lazydf,
+            col,
+            mapping,
+            unmatched,
+            default,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let col = step
+            .properties
+            .get("col")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string();
+        let get_array = |name: &str| {
+            step.properties
+                .get(name)
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .clone()
+        };
+        let mapping = match step.properties.get("lookup") {
+            Some(path) => RecodeMapping::Lookup(path.as_str().unwrap().to_string()),
+            None => RecodeMapping::Lists {
+                from: get_array("from"),
+                to: get_array("to"),
+            },
+        };
+        let unmatched = step
+            .properties
+            .get("unmatched")
+            .map_or(String::from("keep"), |u| u.as_str().unwrap().to_string());
+        let default = step.properties.get("default").cloned();
+        RecodeStep::new(lazydf, col, mapping, unmatched, default)
+    }
+
+    /// Values and new values as two series
+    fn mapping_series(&self) -> Result<(Series, Series), PolarsError> {
+        match &self.mapping {
+            RecodeMapping::Lists { from, to } => {
+                Ok((values_series("from", from), values_series("to", to)))
+            }
+            RecodeMapping::Lookup(path) => {
+                let lookup = CsvReader::from_path(path)
+                    .unwrap_or_else(|e| {
+                        panic!("Error! Cannot read the lookup file {}: {}", path, e)
+                    })
+                    .has_header(true)
+                    .finish()?;
+                assert!(
+                    lookup.width() == 2,
+                    "Error! The lookup file {} should have two columns: the values and their new values",
+                    path
+                );
+                let columns = lookup.get_columns();
+                Ok((columns[0].clone(), columns[1].clone()))
+            }
+        }
+    }
+}
+
+/// Series of the values of a TOML list
+/// Lists of booleans, integers or numbers keep their type, other lists are strings
+fn values_series(name: &str, values: &[Value]) -> Series {
+    if values.iter().all(|v| v.is_bool()) {
+        let values: Vec<bool> = values.iter().map(|v| v.as_bool().unwrap()).collect();
+        Series::new(name, values)
+    } else if values.iter().all(|v| v.is_integer()) {
+        Series::new(name, get_int_array(values.to_vec()))
+    } else if values.iter().all(|v| v.is_integer() || v.is_float()) {
+        let values: Vec<f64> = values.iter().map(get_number).collect();
+        Series::new(name, values)
+    } else {
+        let values: Vec<String> = values
+            .iter()
+            .map(|v| match v.as_str() {
+                Some(s) => s.to_string(),
+                None => v.to_string(),
+            })
+            .collect();
+        Series::new(name, values)
+    }
+}
+
+/// Type of a recoded column: the type of the new values, or the widest type when both are numbers
+fn recode_dtype(column: &DataType, values: &DataType) -> DataType {
+    match (column, values) {
+        (DataType::Float32 | DataType::Float64, v) | (v, DataType::Float32 | DataType::Float64)
+            if v.is_numeric() =>
+        {
+            DataType::Float64
+        }
+        (c, v) if c.is_numeric() && v.is_numeric() => DataType::Int64,
+        (_, v) => v.clone(),
+    }
+}
+
+impl Execute for RecodeStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let (from, to) = self.mapping_series()?;
+        let column_dtype = match self.lazydf.schema()?.get(&self.col) {
+            Some(dtype) => dtype.clone(),
+            None => return Err(PolarsError::NotFound(self.col.clone().into())),
+        };
+        // the values are converted to the type of the column, categories are found by their text
+        let key_dtype = match column_dtype {
+            DataType::Categorical(_) => DataType::Utf8,
+            ref dtype => dtype.clone(),
+        };
+        let mut from = from.cast(&key_dtype)?;
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for (i, value) in from.iter().enumerate() {
+            if value != AnyValue::Null {
+                assert!(
+                    positions.insert(value.to_string(), i).is_none(),
+                    "Error! recode of {} has the value {} more than once",
+                    self.col,
+                    value
+                );
+            }
+        }
+        let dtype = recode_dtype(&column_dtype, to.dtype());
+        let mut to = to.cast(&dtype)?;
+        from.rename("__recode_from");
+        to.rename("__recode_to");
+        let matched = Series::new("__recode_matched", vec![true; from.len()]);
+        let lookup = DataFrame::new(vec![from, to, matched])?
+            .lazy()
+            .filter(col("__recode_from").is_not_null());
+
+        // the rows without a value in the lookup have no match
+        let value = || col(&self.col);
+        let matched = || col("__recode_matched").is_not_null();
+        let unmatched = match self.unmatched.as_str() {
+            "keep" => value().cast(dtype.clone()),
+            "default" => {
+                let default = self.default.as_ref().unwrap();
+                lit(values_series("default", std::slice::from_ref(default)).cast(&dtype)?).first()
+            }
+            _ => lit(Null {}).cast(dtype.clone()),
+        };
+        let mut recoded = when(matched())
+            .then(col("__recode_to"))
+            .otherwise(unmatched);
+        // unmatched values are an error, and kept values that cannot be converted are reported
+        let name = self.col.clone();
+        recoded = match self.unmatched.as_str() {
+            "error" => map_multiple(
+                move |s| {
+                    let missing = s[1].bool()?;
+                    if let Some(row) = missing.into_iter().position(|m| m == Some(true)) {
+                        return Err(PolarsError::ComputeError(
+                            format!(
+                                "recode of {}: the value {} in row {} is not recoded",
+                                name,
+                                s[2].get(row),
+                                row + 1
+                            )
+                            .into(),
+                        ));
+                    }
+                    Ok(std::mem::take(&mut s[0]))
+                },
+                [recoded, value().is_not_null().and(matched().not()), value()],
+                GetOutput::from_type(dtype),
+            ),
+            "keep" if dtype != column_dtype => map_multiple(
+                move |s| {
+                    check_cast(&name, &s[1], &s[0], false)?;
+                    Ok(std::mem::take(&mut s[0]))
+                },
+                [recoded, value()],
+                GetOutput::from_type(dtype),
+            ),
+            _ => recoded,
+        };
+        let new_lazydf = self
+            .lazydf
+            .clone()
+            .left_join(lookup, value().cast(key_dtype), col("__recode_from"))
+            .with_column(recoded.alias(&self.col))
+            .drop_columns(["__recode_to", "__recode_matched"]);
+        Ok(new_lazydf)
+    }
+    fn validate(step: &Steps) {
+        assert!(
+            step.properties.get("col").is_some_and(|c| c.is_str()),
+            "Error! recode does not contain property: col"
+        );
+        match step.properties.get("lookup") {
+            Some(lookup) => {
+                assert!(lookup.is_str(), "Error! recode lookup should be a path");
+                assert!(
+                    !step.properties.contains_key("from") && !step.properties.contains_key("to"),
+                    "Error! recode should contain either lookup or the lists from and to, not both"
+                );
+            }
+            None => {
+                let from = step.properties.get("from").and_then(|f| f.as_array());
+                let to = step.properties.get("to").and_then(|t| t.as_array());
+                assert!(
+                    from.is_some_and(|f| !f.is_empty()),
+                    "Error! recode does not contain property: from (a list of values)"
+                );
+                assert!(
+                    to.is_some_and(|t| !t.is_empty()),
+                    "Error! recode does not contain property: to (a list of values)"
+                );
+                assert!(
+                    from.unwrap().len() == to.unwrap().len(),
+                    "Error! recode lists from and to should have the same length"
+                );
+            }
+        }
+        let unmatched = step.properties.get("unmatched");
+        if let Some(unmatched) = unmatched {
+            assert!(
+                unmatched
+                    .as_str()
+                    .is_some_and(|u| RECODE_UNMATCHED.contains(&u)),
+                "Error! recode unmatched should be one of {:?}",
+                RECODE_UNMATCHED
+            );
+        }
+        assert!(
+            (unmatched.and_then(|u| u.as_str()) == Some("default"))
+                == step.properties.contains_key("default"),
+            "Error! recode should contain property default when (and only when) unmatched is default"
+        );
+    }
+}
+
+/// cast
+/// cast one or more columns into another data type: string, integers, floats, boolean, categorical, date, datetime or duration.
+/// Values that cannot be converted become null and are reported, or make the step fail in strict mode
+pub struct CastStep {
+    lazydf: LazyFrame,
+    cols: Vec<String>,
+    to: String,
+    // time unit of datetime and duration: ms, us or ns
+    unit: String,
+    // format used to parse dates and datetimes from strings
+    format: Option<String>,
+    // fail when a value cannot be converted, instead of making it null
+    strict: bool,
+}
+impl CastStep {
+    pub fn new(
+        lazydf: LazyFrame,
+        cols: Vec<String>,
+        to: String,
+        unit: String,
+        format: Option<String>,
+        strict: bool,
+    ) -> Self {
+        CastStep {
+            lazydf,
+            cols,
+            to,
+            unit,
+            format,
+            strict,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let get_str = |name: &str| {
+            step.properties
+                .get(name)
+                .map(|v| v.as_str().unwrap().to_string())
+        };
+        let cols = get_columns(step.properties.get("col").unwrap());
+        let strict = step
+            .properties
+            .get("strict")
+            .is_some_and(|s| s.as_bool().unwrap());
+        CastStep::new(
+            lazydf,
+            cols,
+            get_str("to").unwrap(),
+            get_str("unit").unwrap_or_else(|| String::from("us")),
+            get_str("format"),
+            strict,
+        )
+    }
+
+    fn dtype(&self) -> DataType {
+        get_dtype(&self.to, &self.unit)
+    }
+}
+
+/// Convert a series to a data type. Values that cannot be converted are null
+/// Strings are parsed as dates and datetimes with the format (guessed if not given),
+/// and as booleans from true/false, yes/no and 1/0
+fn cast_series(
+    series: &Series,
+    dtype: &DataType,
+    format: Option<&str>,
+) -> Result<Series, PolarsError> {
+    match (series.dtype(), dtype) {
+        (DataType::Utf8, DataType::Date) => Ok(series.utf8()?.as_date(format)?.into_series()),
+        (DataType::Utf8, DataType::Datetime(unit, _)) => {
+            Ok(series.utf8()?.as_datetime(format, *unit)?.into_series())
+        }
+        (DataType::Utf8, DataType::Boolean) => {
+            let mut values: BooleanChunked = series
+                .utf8()?
+                .into_iter()
+                .map(|v| match v.map(|v| v.trim().to_lowercase()).as_deref() {
+                    Some("true" | "yes" | "1") => Some(true),
+                    Some("false" | "no" | "0") => Some(false),
+                    _ => None,
+                })
+                .collect();
+            values.rename(series.name());
+            Ok(values.into_series())
+        }
+        _ => series.cast(dtype),
+    }
+}
+/// Report the values that could not be cast and are now null
+/// In strict mode they are an error, showing the first of them
+fn check_cast(
+    name: &str,
+    original: &Series,
+    cast: &Series,
+    strict: bool,
+) -> Result<(), PolarsError> {
+    let lost = original.is_not_null() & cast.is_null();
+    let count = lost.sum().unwrap_or(0);
+    if count == 0 {
+        return Ok(());
+    }
+    let row = lost.into_iter().position(|l| l == Some(true)).unwrap();
+    let message = format!(
+        "{}: {} of the values cannot be cast to {}, the first is {} in row {}",
+        name,
+        count,
+        cast.dtype(),
+        original.get(row),
+        row + 1
+    );
+    match strict {
+        true => Err(PolarsError::ComputeError(message.into())),
+        false => {
+            warn!("{}. They are now null", message);
+            Ok(())
+        }
+    }
+}
+impl Execute for CastStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let dtype = self.dtype();
+        let cast_cols: Vec<Expr> = self
+            .cols
+            .iter()
+            .map(|c| {
+                let (name, to, format, strict) =
+                    (c.clone(), dtype.clone(), self.format.clone(), self.strict);
+                col(c)
+                    .map(
+                        move |s| {
+                            let cast = cast_series(&s, &to, format.as_deref())?;
+                            check_cast(&name, &s, &cast, strict)?;
+                            Ok(cast)
+                        },
+                        GetOutput::from_type(dtype.clone()),
+                    )
+                    .alias(c)
+            })
+            .collect();
+        let new_lazydf = self.lazydf.clone().with_columns(cast_cols);
+        Ok(new_lazydf)
+    }
+    fn validate(step: &Steps) {
+        assert!(
+            step.properties.get("col").is_some_and(is_columns),
+            "Error! cast does not contain property: col (a column name or a list of column names)"
+        );
+        assert!(
+            step.properties
+                .get("to")
+                .and_then(|t| t.as_str())
+                .is_some_and(|t| DATA_TYPES.contains(&t)),
+            "Error! cast to should be one of {:?}",
+            DATA_TYPES
+        );
+        if let Some(unit) = step.properties.get("unit") {
+            assert!(
+                unit.as_str()
+                    .is_some_and(|u| ["ms", "us", "ns"].contains(&u)),
+                "Error! cast unit should be ms, us or ns"
+            );
+        }
+        if let Some(format) = step.properties.get("format") {
+            assert!(format.is_str(), "Error! cast format should be a string");
+        }
+        if let Some(strict) = step.properties.get("strict") {
+            assert!(
+                strict.is_bool(),
+                "Error! cast strict should be true or false"
+            );
+        }
+    }
+}
+
+/// Pivot table
+/// pivot a table from long to wide format
+pub struct PivotStep {
+    lazydf: LazyFrame,
+    values: Vec<String>,
+    index: Vec<String>,
+    columns: Vec<String>,
+    aggregation: String,
+    sort_columns: bool,
+}
+impl PivotStep {
+    pub fn new(
+        lazydf: LazyFrame,
+        values: Vec<String>,
+        index: Vec<String>,
+        columns: Vec<String>,
+        aggregation: String,
+        sort_columns: bool,
+    ) -> Self {
+        PivotStep {
+            lazydf,
+            values,
+            index,
+            columns,
+            aggregation,
+            sort_columns,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let values = get_string_array(
+            step.properties
+                .get("values")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .clone(),
+        );
+        let index = get_string_array(
+            step.properties
+                .get("index")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .clone(),
+        );
+        let columns = get_string_array(
+            step.properties
+                .get("columns")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .clone(),
+        );
+        let aggregation = step
+            .properties
+            .get("aggregation")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string();
+        let sort_columns = step
+            .properties
+            .get("sort_columns")
+            .unwrap()
+            .as_bool()
+            .unwrap();
+        PivotStep::new(lazydf, values, index, columns, aggregation, sort_columns)
+    }
+}
+impl Execute for PivotStep {
     fn execute(&self) -> Result<LazyFrame, PolarsError> {
         let df = self
             .lazydf
@@ -831,7 +2993,7 @@ impl Execute for PivotStep {
             self.index.clone(),
             self.columns.clone(),
             agg_fn,
-            self.sort_columns.clone(),
+            self.sort_columns,
         )
         .unwrap();
         let new_lazydf = out.lazy();
@@ -872,10 +3034,7 @@ impl UniqueStep {
         UniqueStep { lazydf, cols }
     }
     pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
-        let all_col = match step.properties.get("cols") {
-            None => true,
-            _ => false
-        };
+        let all_col = step.properties.get("cols").is_none();
         let cols:Option<Vec<String>> = match all_col {
             true => None,
             false => {
@@ -900,8 +3059,7 @@ impl Execute for UniqueStep {
                 .unique_stable(self.cols.clone(), UniqueKeepStrategy::First);
         Ok(new_lazydf)
     }
-    fn validate(step: &Steps) {
-    }
+    fn validate(_step: &Steps) {}
 }
 
 
@@ -2,28 +2,43 @@
 // Author: David Issa Mattos
 // Mantainer: David Issa Mattos
 
+use crate::compression::*;
 use crate::steps::*;
+use crate::utils::*;
+use log::info;
 use polars::prelude::*;
+use rand::seq::SliceRandom;
 
-pub const OUTPUT_OP: &[&str] = &["save_csv", "preview"];
+pub const OUTPUT_OP: &[&str] = &["save_csv", "split", "preview", "explain"];
 
 /// Save csv to file
-/// Saves a DataFrame in a csv file Polars
+/// Saves a DataFrame in a csv file Polars, or in stdout if the path is "-"
+/// The file is compressed with gzip, zstd or bzip2 when asked, or when the path ends with .gz, .zst or .bz2
 pub struct SaveCsvStep {
     lazydf: LazyFrame,
     delimiter: u8,
     header: bool,
     path: String,
+    compression: Compression,
+    // level of the compression, the default of the compression if None
+    level: Option<u32>,
 }
 impl SaveCsvStep {
     pub fn new(lazydf: LazyFrame, path: &str, delimiter: u8, header: bool) -> Self {
         SaveCsvStep {
-            lazydf: lazydf,
-            delimiter: delimiter,
-            header: header,
+            lazydf,
+            delimiter,
+            header,
             path: String::from(path),
+            compression: Compression::from_extension(path),
+            level: None,
         }
     }
+    pub fn with_compression(mut self, compression: Compression, level: Option<u32>) -> Self {
+        self.compression = compression;
+        self.level = level;
+        self
+    }
     pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
         let path = step
             .properties
@@ -40,51 +55,525 @@ impl SaveCsvStep {
             .unwrap()
             .to_string()
             .as_bytes()
-            .get(0)
-            .unwrap()
-            .clone();
+            .first()
+            .copied()
+            .unwrap();
         let header = step.properties.get("header").unwrap().as_bool().unwrap();
-        SaveCsvStep::new(lazydf, &path, delimiter, header)
+        let compression = step
+            .properties
+            .get("compression")
+            .map_or(Compression::from_extension(&path), |c| {
+                Compression::from_name(c.as_str().unwrap())
+            });
+        let level = step
+            .properties
+            .get("level")
+            .map(|l| l.as_integer().unwrap() as u32);
+        SaveCsvStep::new(lazydf, &path, delimiter, header).with_compression(compression, level)
     }
 }
 impl Execute for SaveCsvStep {
     fn execute(&self) -> Result<LazyFrame, PolarsError> {
         let mut df = self.lazydf.clone().collect()?;
-        let mut file = std::fs::File::create(&self.path)?;
-        CsvWriter::new(&mut file)
+        // the path "-" writes to stdout, so the output can be piped to other programs
+        let writer: Box<dyn std::io::Write> = match self.path.as_str() {
+            "-" => Box::new(std::io::stdout().lock()),
+            path => Box::new(std::fs::File::create(path)?),
+        };
+        let mut writer = CompressedWriter::new(writer, self.compression, self.level)?;
+        CsvWriter::new(&mut writer)
             .has_header(self.header)
             .with_delimiter(self.delimiter)
             .finish(&mut df)?;
+        writer.finish()?;
         Ok(self.lazydf.clone())
     }
     fn validate(step: &Steps) {
         assert!(step.properties.contains_key("path"));
         assert!(step.properties.contains_key("delimiter"));
         assert!(step.properties.contains_key("header"));
+        let compression = step.properties.get("compression");
+        if let Some(compression) = compression {
+            assert!(
+                compression
+                    .as_str()
+                    .is_some_and(|c| COMPRESSIONS.contains(&c)),
+                "Error! save_csv compression should be one of {:?}",
+                COMPRESSIONS
+            );
+        }
+        if let Some(level) = step.properties.get("level") {
+            let compression = match compression {
+                Some(c) => Compression::from_name(c.as_str().unwrap()),
+                None => Compression::from_extension(
+                    step.properties
+                        .get("path")
+                        .and_then(|p| p.as_str())
+                        .unwrap_or_default(),
+                ),
+            };
+            assert!(
+                compression != Compression::None,
+                "Error! save_csv level needs a compression, given by compression or by the extension of the path"
+            );
+            let levels = compression.levels();
+            assert!(
+                level
+                    .as_integer()
+                    .is_some_and(|l| l >= 0 && levels.contains(&(l as u32))),
+                "Error! save_csv level of {} should be between {} and {}",
+                compression.name(),
+                levels.start(),
+                levels.end()
+            );
+        }
+    }
+}
+
+/// Split
+/// Randomly splits the rows in partitions (e.g. train, validation and test) and saves each partition in a csv file.
+/// When grouped, all the rows with the same value of the group column are saved in the same partition
+pub struct SplitStep {
+    lazydf: LazyFrame,
+    // path of the files, where {partition} is replaced by the name of the partition
+    path: String,
+    proportions: Vec<(String, f64)>,
+    group: Option<String>,
+    seed: Option<u64>,
+    delimiter: u8,
+    header: bool,
+}
+impl SplitStep {
+    pub fn new(
+        lazydf: LazyFrame,
+        path: String,
+        proportions: Vec<(String, f64)>,
+        group: Option<String>,
+        seed: Option<u64>,
+        delimiter: u8,
+        header: bool,
+    ) -> Self {
+        SplitStep {
+            lazydf,
+            path,
+            proportions,
+            group,
+            seed,
+            delimiter,
+            header,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let path = step
+            .properties
+            .get("path")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string();
+        let proportions = step
+            .properties
+            .get("proportions")
+            .unwrap()
+            .as_table()
+            .unwrap()
+            .iter()
+            .map(|(name, p)| (name.clone(), get_number(p)))
+            .collect();
+        let group = step
+            .properties
+            .get("group")
+            .map(|g| g.as_str().unwrap().to_string());
+        let seed = step
+            .properties
+            .get("seed")
+            .map(|s| s.as_integer().unwrap() as u64);
+        let delimiter = step
+            .properties
+            .get("delimiter")
+            .map_or(b',', |d| d.as_str().unwrap().as_bytes()[0]);
+        let header = step
+            .properties
+            .get("header")
+            .is_none_or(|h| h.as_bool().unwrap());
+        SplitStep::new(lazydf, path, proportions, group, seed, delimiter, header)
     }
 }
+impl Execute for SplitStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let df = self.lazydf.clone().collect()?;
+        // the units that are shuffled and assigned to the partitions, groups of rows or single rows
+        let mut units: Vec<Vec<IdxSize>> = match &self.group {
+            Some(group) => group_rows(&df, std::slice::from_ref(group))?,
+            None => (0..df.height() as IdxSize).map(|i| vec![i]).collect(),
+        };
+        units.shuffle(&mut get_rng(self.seed));
+
+        let mut start = 0;
+        let mut cumulative = 0.0;
+        for (i, (name, proportion)) in self.proportions.iter().enumerate() {
+            cumulative += proportion;
+            // the last partition takes the remaining units, so rounding never drops rows
+            let end = match i == self.proportions.len() - 1 {
+                true => units.len(),
+                false => ((cumulative * units.len() as f64).round() as usize).min(units.len()),
+            };
+            let mut rows: Vec<IdxSize> = units[start..end].concat();
+            rows.sort_unstable();
+            start = end;
 
-/// Preview header
-/// Pretty prints a DataFrame in the command line using the default format display printer of Polars
+            let mut partition = df.take(&IdxCa::from_vec("rows", rows))?;
+            let path = self.path.replace("{partition}", name);
+            let file = std::fs::File::create(&path)?;
+            let mut writer = CompressedWriter::new(file, Compression::from_extension(&path), None)?;
+            CsvWriter::new(&mut writer)
+                .has_header(self.header)
+                .with_delimiter(self.delimiter)
+                .finish(&mut partition)?;
+            writer.finish()?;
+            info!(
+                "Saved the partition {} ({} rows) in {}",
+                name,
+                partition.height(),
+                path
+            );
+        }
+        Ok(self.lazydf.clone())
+    }
+    fn validate(step: &Steps) {
+        let path = step.properties.get("path");
+        assert!(
+            path.and_then(|p| p.as_str())
+                .is_some_and(|p| p.contains("{partition}")),
+            "Error! split path should contain {{partition}}, which is replaced by the name of each partition"
+        );
+        let proportions = step
+            .properties
+            .get("proportions")
+            .and_then(|p| p.as_table())
+            .expect("Error! split should contain a table of proportions, e.g. { train = 0.8, test = 0.2 }");
+        assert!(
+            !proportions.is_empty()
+                && proportions
+                    .values()
+                    .all(|p| (p.is_float() || p.is_integer()) && get_number(p) >= 0.0),
+            "Error! split proportions should be non-negative numbers"
+        );
+        let total: f64 = proportions.values().map(get_number).sum();
+        assert!(
+            (total - 1.0).abs() < 1e-6,
+            "Error! split proportions should sum to 1, not {}",
+            total
+        );
+        if let Some(group) = step.properties.get("group") {
+            assert!(group.is_str(), "Error! split group should be a column name");
+        }
+        if let Some(seed) = step.properties.get("seed") {
+            assert!(
+                seed.as_integer().is_some_and(|s| s >= 0),
+                "Error! split seed should be a non-negative integer"
+            );
+        }
+        if let Some(delimiter) = step.properties.get("delimiter") {
+            assert!(
+                delimiter.as_str().is_some_and(|d| d.len() == 1),
+                "Error! split delimiter should be a single character"
+            );
+        }
+        if let Some(header) = step.properties.get("header") {
+            assert!(
+                header.is_bool(),
+                "Error! split header should be true or false"
+            );
+        }
+    }
+}
+
+/// Preview
+/// Pretty prints some rows of the frame in the command line using the display printer of Polars.
+/// Optionally prints a title, the schema and the total number of rows
 pub struct PreviewStep {
     lazydf: LazyFrame,
+    options: PreviewOptions,
+}
+/// What is printed by a preview
+pub struct PreviewOptions {
+    pub rows: usize,
+    // head, tail or random
+    pub mode: String,
+    pub seed: Option<u64>,
+    pub columns: Option<Vec<String>>,
+    // maximum number of characters printed in a cell
+    pub width: Option<usize>,
+    pub schema: bool,
+    pub count: bool,
+    pub title: Option<String>,
+}
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        PreviewOptions {
+            rows: 1000,
+            mode: String::from("head"),
+            seed: None,
+            columns: None,
+            width: None,
+            schema: false,
+            count: false,
+            title: None,
+        }
+    }
 }
 impl PreviewStep {
-    pub fn new(lazydf: LazyFrame) -> Self {
-        PreviewStep { lazydf: lazydf }
+    pub fn new(lazydf: LazyFrame, options: PreviewOptions) -> Self {
+        PreviewStep { lazydf, options }
     }
     pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
-        PreviewStep::new(lazydf)
+        let properties = &step.properties;
+        let defaults = PreviewOptions::default();
+        let get_bool = |name: &str| properties.get(name).is_some_and(|v| v.as_bool().unwrap());
+        let options = PreviewOptions {
+            rows: properties
+                .get("rows")
+                .map_or(defaults.rows, |v| v.as_integer().unwrap() as usize),
+            mode: properties
+                .get("mode")
+                .map_or(defaults.mode, |v| v.as_str().unwrap().to_string()),
+            seed: properties
+                .get("seed")
+                .map(|v| v.as_integer().unwrap() as u64),
+            columns: properties.get("columns").map(get_columns),
+            width: properties
+                .get("width")
+                .map(|v| v.as_integer().unwrap() as usize),
+            schema: get_bool("schema"),
+            count: get_bool("count"),
+            title: properties
+                .get("title")
+                .map(|v| v.as_str().unwrap().to_string()),
+        };
+        PreviewStep::new(lazydf, options)
     }
 }
 impl Execute for PreviewStep {
     fn execute(&self) -> Result<LazyFrame, PolarsError> {
-        let mut df = self.lazydf.clone().fetch(1000).unwrap();
-        println!("{}", df);
+        let options = &self.options;
+        let lazydf = match &options.columns {
+            Some(columns) => self
+                .lazydf
+                .clone()
+                .select(get_array_columns(columns.clone())),
+            None => self.lazydf.clone(),
+        };
+        let rows = options.rows as IdxSize;
+        let df = match options.mode.as_str() {
+            "tail" => lazydf.clone().tail(rows).collect()?,
+            "random" => {
+                let df = lazydf.clone().collect()?;
+                let amount = options.rows.min(df.height());
+                let mut indices: Vec<IdxSize> =
+                    rand::seq::index::sample(&mut get_rng(options.seed), df.height(), amount)
+                        .iter()
+                        .map(|i| i as IdxSize)
+                        .collect();
+                indices.sort_unstable();
+                df.take(&IdxCa::from_vec("rows", indices))?
+            }
+            _ => lazydf.clone().limit(rows).collect()?,
+        };
+
+        // everything is printed at once, so previews running in parallel are not mixed
+        let mut output = String::new();
+        if let Some(title) = &options.title {
+            output.push_str(&format!("{}\n", title));
+        }
+        if options.schema {
+            output.push_str("Schema:\n");
+            for (name, dtype) in df.schema().iter() {
+                output.push_str(&format!("  {}: {}\n", name, dtype));
+            }
+        }
+        if options.count {
+            let total = lazydf.select([count()]).collect()?;
+            let total = total.get_columns()[0].get(0).to_string();
+            output.push_str(&format!("Rows: {}\n", total));
+        }
+        output.push_str(&format_frame(&df, options.rows, options.width));
+        println!("{}", output);
         Ok(self.lazydf.clone())
     }
-    fn validate(step: &Steps) {}
+    fn validate(step: &Steps) {
+        let properties = &step.properties;
+        for name in ["rows", "width", "seed"] {
+            if let Some(value) = properties.get(name) {
+                assert!(
+                    value.as_integer().is_some_and(|v| v >= 0),
+                    "Error! preview {} should be a non-negative integer",
+                    name
+                );
+            }
+        }
+        if let Some(mode) = properties.get("mode") {
+            assert!(
+                mode.as_str()
+                    .is_some_and(|m| ["head", "tail", "random"].contains(&m)),
+                "Error! preview mode should be head, tail or random"
+            );
+        }
+        if let Some(columns) = properties.get("columns") {
+            assert!(
+                is_columns(columns),
+                "Error! preview columns should be a column name or a non-empty list of column names"
+            );
+        }
+        for name in ["schema", "count"] {
+            if let Some(value) = properties.get(name) {
+                assert!(
+                    value.is_bool(),
+                    "Error! preview {} should be true or false",
+                    name
+                );
+            }
+        }
+        if let Some(title) = properties.get("title") {
+            assert!(title.is_str(), "Error! preview title should be a string");
+        }
+    }
 }
+/// Format a frame as a table, printing up to a number of rows and all the columns
+/// Optionally limits the number of characters printed in a cell.
+/// The table is built here instead of with the format of polars, which is configured with environment
+/// variables shared by all the steps running in parallel
+pub fn format_frame(df: &DataFrame, rows: usize, width: Option<usize>) -> String {
+    let mut shown = df.head(Some(rows));
+    shown.rechunk();
+    let cell = |value: AnyValue| {
+        let text = match value {
+            AnyValue::Null => String::from("null"),
+            AnyValue::Utf8(s) => s.to_string(),
+            value => value.to_string(),
+        };
+        match width {
+            Some(width) if text.chars().count() > width => {
+                format!("{}…", text.chars().take(width).collect::<String>())
+            }
+            _ => text,
+        }
+    };
+    let columns: Vec<Vec<String>> = shown
+        .get_columns()
+        .iter()
+        .map(|s| {
+            let mut texts = vec![
+                s.name().to_string(),
+                String::from("---"),
+                s.dtype().to_string(),
+            ];
+            texts.extend(s.iter().map(cell));
+            texts
+        })
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .map(|texts| texts.iter().map(|t| t.chars().count()).max().unwrap_or(0))
+        .collect();
+    let line = |left: &str, fill: &str, middle: &str, right: &str| {
+        let parts: Vec<String> = widths.iter().map(|w| fill.repeat(w + 2)).collect();
+        format!("{}{}{}\n", left, parts.join(middle), right)
+    };
+    let row = |i: usize| {
+        let cells: Vec<String> = columns
+            .iter()
+            .zip(&widths)
+            .map(|(texts, w)| format!(" {:<w$} ", texts[i], w = w))
+            .collect();
+        format!("│{}│\n", cells.join("┆"))
+    };
+
+    let mut table = format!("shape: ({}, {})\n", df.height(), df.width());
+    if df.width() == 0 {
+        return table;
+    }
+    table.push_str(&line("┌", "─", "┬", "┐"));
+    for i in 0..3 {
+        table.push_str(&row(i));
+    }
+    table.push_str(&line("╞", "═", "╪", "╡"));
+    for i in 3..3 + shown.height() {
+        if i > 3 {
+            table.push_str(&line("├", "╌", "┼", "┤"));
+        }
+        table.push_str(&row(i));
+    }
+    if df.height() > shown.height() {
+        table.push_str(&line("├", "╌", "┼", "┤"));
+        let cells: Vec<String> = widths
+            .iter()
+            .map(|w| format!(" {:<w$} ", "…", w = w))
+            .collect();
+        table.push_str(&format!("│{}│\n", cells.join("┆")));
+    }
+    table.push_str(&line("└", "─", "┴", "┘"));
+    table.pop();
+    table
+}
+
+/// Explain
+/// Prints the unoptimized and optimized logical plans of the frame in the command line.
+/// Optionally writes the optimized plan as a Graphviz DOT file
+pub struct ExplainStep {
+    lazydf: LazyFrame,
+    dot: Option<String>,
+}
+impl ExplainStep {
+    pub fn new(lazydf: LazyFrame, dot: Option<String>) -> Self {
+        ExplainStep { lazydf, dot }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let dot = step
+            .properties
+            .get("dot")
+            .map(|v| v.as_str().unwrap().to_string());
+        ExplainStep::new(lazydf, dot)
+    }
+}
+impl Execute for ExplainStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        println!("{}", describe_plans(&self.lazydf)?);
+        if let Some(path) = &self.dot {
+            std::fs::write(path, self.lazydf.to_dot(true)?)?;
+        }
+        Ok(self.lazydf.clone())
+    }
+    fn validate(step: &Steps) {
+        if let Some(dot) = step.properties.get("dot") {
+            assert!(dot.is_str(), "Error! explain property dot should be a path");
+        }
+    }
+}
+
+/// Describe the logical plans of a frame
+/// Returns the unoptimized and the optimized plans. The frame was collected at the in-memory scans (DF [...]) of the plan,
+/// which are marked since the optimizations cannot go past them
+pub fn describe_plans(lazydf: &LazyFrame) -> Result<String, PolarsError> {
+    let plan = mark_materialized(&lazydf.describe_plan());
+    let optimized = mark_materialized(&lazydf.describe_optimized_plan()?);
+    Ok(format!(
+        "Logical plan:\n{}\nOptimized logical plan:\n{}",
+        plan, optimized
+    ))
+}
+
+fn mark_materialized(plan: &str) -> String {
+    plan.lines()
+        .map(|line| match line.trim_start().starts_with("DF [") {
+            true => format!("{}  <-- materialized", line),
+            false => line.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+        + "\n"
+}
+
 // mod tests_preview {
 //     use super::*;
 //     //Just testing if it does not fail
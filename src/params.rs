@@ -0,0 +1,228 @@
+// oxidf pipeline parameters
+// Author: David Issa Mattos
+// Mantainer: David Issa Mattos
+
+use crate::steps::*;
+use log::warn;
+use std::env;
+use toml::value::Table;
+use toml::Value;
+
+/// Pipeline parameters
+/// Values that can be used in any string property of the steps with the `${name}` syntax.
+/// The value of a parameter is taken from (in order of priority):
+/// * the command line, with `--param name=value`
+/// * the environment variable `OXIDF_NAME` (the name in upper case)
+/// * the default given in the `[params]` section of the TOML file
+///
+/// The default also gives the type of the parameter, so overrides are parsed to the same type
+pub struct Params {
+    values: Table,
+    // values given in the command line, applied again over the defaults of the included files
+    overrides: Vec<(String, String)>,
+}
+impl Params {
+    pub fn new(defaults: Option<&Table>, overrides: &[String]) -> Result<Self, String> {
+        let mut parsed: Vec<(String, String)> = vec![];
+        for item in overrides {
+            let (name, s) = item.split_once('=').ok_or(format!(
+                "The parameter '{}' should be given as key=value",
+                item
+            ))?;
+            parsed.push((name.trim().to_string(), s.to_string()));
+        }
+        let mut params = Params {
+            values: Table::new(),
+            overrides: parsed,
+        };
+        // parameters not declared in [params] are strings, unless an included file declares them
+        for (name, s) in &params.overrides {
+            params.values.insert(name.clone(), Value::String(s.clone()));
+        }
+        params.declare(defaults)?;
+        Ok(params)
+    }
+
+    /// Add the defaults of the parameters declared in a file
+    /// The command line and the environment take priority over the defaults
+    fn declare(&mut self, defaults: Option<&Table>) -> Result<(), String> {
+        for (name, default) in defaults.cloned().unwrap_or_default() {
+            let given = self
+                .overrides
+                .iter()
+                .rev()
+                .find(|(n, _)| *n == name)
+                .map(|(_, s)| s.clone())
+                .or_else(|| env::var(env_name(&name)).ok());
+            let value = match given {
+                Some(s) => parse_typed(&name, &s, &default)?,
+                None => default,
+            };
+            self.values.insert(name, value);
+        }
+        Ok(())
+    }
+
+    /// Warn about the parameters given in the command line that no file declares
+    /// Arguments:
+    /// * the names of the parameters declared in the pipeline and in all the included files
+    pub fn warn_undeclared(&self, declared: &[String]) {
+        for (name, _) in &self.overrides {
+            if !declared.contains(name) {
+                warn!(
+                    "The parameter '{}' is not declared in [params], using it as a string",
+                    name
+                );
+            }
+        }
+    }
+
+    /// Parameters of an included pipeline
+    /// The included pipeline sees all parameters of the including one. Its own defaults take priority over them,
+    /// but the command line and the environment still take priority over these defaults.
+    /// The values given in the `params` property of the include step take priority over everything
+    pub fn scoped(
+        &self,
+        defaults: Option<&Table>,
+        values: Option<&Table>,
+    ) -> Result<Params, String> {
+        let mut scoped = Params {
+            values: self.values.clone(),
+            overrides: self.overrides.clone(),
+        };
+        scoped.declare(defaults)?;
+        let defaults = defaults.cloned().unwrap_or_default();
+        for (name, value) in values.cloned().unwrap_or_default() {
+            let value = match (defaults.get(&name), value) {
+                (Some(default), Value::Integer(i)) if default.is_float() => Value::Float(i as f64),
+                (Some(default), value) if !value.same_type(default) => {
+                    return Err(format!(
+                        "The parameter '{}' should be of type {} but got {}",
+                        name,
+                        default.type_str(),
+                        value
+                    ))
+                }
+                (_, value) => value,
+            };
+            scoped.values.insert(name, value);
+        }
+        Ok(scoped)
+    }
+
+    /// Value of a parameter
+    /// Parameters that are not declared in the TOML file can still be given in the environment
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.values.get(name) {
+            Some(v) => Some(v.clone()),
+            None => env::var(env_name(name)).ok().map(Value::String),
+        }
+    }
+
+    /// Replace the placeholders in the properties of all steps
+    pub fn apply(&self, steps: &mut [Steps]) -> Result<(), String> {
+        for (i, step) in steps.iter_mut().enumerate() {
+            for (key, value) in step.properties.iter_mut() {
+                *value = self.substitute(value).map_err(|e| {
+                    format!("Step {} ({}) property {}: {}", i, step.operation, key, e)
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the placeholders in a TOML value
+    /// A string made only of one placeholder takes the value (and type) of the parameter.
+    /// Otherwise the parameters are formatted into the string
+    pub fn substitute(&self, value: &Value) -> Result<Value, String> {
+        match value {
+            Value::String(s) => {
+                let whole = s
+                    .trim()
+                    .strip_prefix("${")
+                    .and_then(|n| n.strip_suffix('}'));
+                if let Some(name) = whole.filter(|n| !n.contains('}')) {
+                    return self.get(name).ok_or(unresolved(name));
+                }
+                Ok(Value::String(self.format(s)?))
+            }
+            Value::Array(items) => {
+                let items: Result<Vec<Value>, String> =
+                    items.iter().map(|v| self.substitute(v)).collect();
+                Ok(Value::Array(items?))
+            }
+            Value::Table(table) => {
+                let mut new_table = Table::new();
+                for (k, v) in table {
+                    new_table.insert(k.clone(), self.substitute(v)?);
+                }
+                Ok(Value::Table(new_table))
+            }
+            other => Ok(other.clone()),
+        }
+    }
+
+    fn format(&self, s: &str) -> Result<String, String> {
+        let mut out = String::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("${") {
+            out.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or(format!("Unterminated placeholder in '{}'", s))?;
+            let name = &rest[start + 2..start + end];
+            match self.get(name).ok_or(unresolved(name))? {
+                Value::String(v) => out.push_str(&v),
+                Value::Integer(v) => out.push_str(&v.to_string()),
+                Value::Float(v) => out.push_str(&v.to_string()),
+                Value::Boolean(v) => out.push_str(&v.to_string()),
+                _ => {
+                    return Err(format!(
+                        "The parameter '{}' cannot be formatted into the string '{}'",
+                        name, s
+                    ))
+                }
+            }
+            rest = &rest[start + end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+fn env_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("OXIDF_{}", name.to_uppercase())
+}
+
+fn unresolved(name: &str) -> String {
+    format!(
+        "Unresolved parameter ${{{}}}. Declare it in [params], pass --param {}=... or set {}",
+        name,
+        name,
+        env_name(name)
+    )
+}
+
+/// Parse a string into a TOML value of the same type as the default value of the parameter
+fn parse_typed(name: &str, s: &str, default: &Value) -> Result<Value, String> {
+    let value = match default {
+        Value::String(_) => return Ok(Value::String(s.to_string())),
+        _ => toml::from_str::<Table>(&format!("v = {}", s))
+            .ok()
+            .and_then(|mut t| t.remove("v")),
+    };
+    match value {
+        Some(Value::Integer(i)) if default.is_float() => Ok(Value::Float(i as f64)),
+        Some(v) if v.same_type(default) => Ok(v),
+        _ => Err(format!(
+            "The parameter '{}' should be of type {} but got '{}'",
+            name,
+            default.type_str(),
+            s
+        )),
+    }
+}
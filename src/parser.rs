@@ -1,12 +1,14 @@
+use crate::graph::*;
 use crate::input::*;
 use crate::operations::*;
 use crate::output::*;
+use crate::params::*;
 use crate::steps::*;
-use crate::merge::*;
+use crate::summary::*;
 use core::panic;
 use std::fs;
 use std::io;
-use toml;
+use std::path::{Path, PathBuf};
 
 /// Parse a TOML file into a Pipeline
 /// Read a TOML file and parse it according to a Pipeline struct
@@ -18,6 +20,117 @@ pub fn parse_toml(path: &str) -> Result<Pipeline, io::Error> {
     Ok(toml_content)
 }
 
+/// Load a pipeline
+/// Parse the TOML file, replace the parameters and splice the steps of the included pipelines
+/// Arguments:
+/// * path to the TOML file
+/// * the parameters given in the command line as key=value
+pub fn load_pipeline(path: &str, overrides: &[String]) -> Result<Pipeline, String> {
+    let mut pipeline =
+        parse_toml(path).map_err(|e| format!("Error parsing the toml file {}: {}", path, e))?;
+    let params = Params::new(pipeline.params.as_ref(), overrides)?;
+    let chain = vec![canonical_path(Path::new(path))?];
+    let steps = std::mem::take(&mut pipeline.steps);
+    let mut declared: Vec<String> = pipeline
+        .params
+        .as_ref()
+        .map_or(vec![], |p| p.keys().cloned().collect());
+    pipeline.steps = expand_includes(steps, &params, &chain, &mut declared)?;
+    params.warn_undeclared(&declared);
+    Ok(pipeline)
+}
+
+/// Replace the parameters in the steps and splice the steps of the include steps
+/// Arguments:
+/// * the steps of a pipeline file
+/// * the parameters of this file
+/// * the chain of files including each other, the last one being the file of the steps
+/// * the names of the parameters declared so far, where those of the included files are added
+///
+/// The name of an include step is given to the last included step, and its input to the first one
+fn expand_includes(
+    mut steps: Vec<Steps>,
+    params: &Params,
+    chain: &[PathBuf],
+    declared: &mut Vec<String>,
+) -> Result<Vec<Steps>, String> {
+    params.apply(&mut steps).map_err(|e| with_chain(e, chain))?;
+    let mut expanded: Vec<Steps> = vec![];
+    for step in steps {
+        if step.operation != "include" {
+            expanded.push(step);
+            continue;
+        }
+        let relative = step
+            .properties
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| {
+                with_chain(
+                    String::from("include does not contain property: path"),
+                    chain,
+                )
+            })?;
+        // relative paths are resolved against the directory of the including file
+        let including = chain.last().unwrap();
+        let path = canonical_path(&including.parent().unwrap().join(relative))
+            .map_err(|e| with_chain(e, chain))?;
+        let mut new_chain = chain.to_vec();
+        new_chain.push(path.clone());
+        if chain.contains(&path) {
+            return Err(with_chain(String::from("Include cycle"), &new_chain));
+        }
+        let content =
+            fs::read_to_string(&path).map_err(|e| with_chain(e.to_string(), &new_chain))?;
+        let sub: SubPipeline = toml::from_str(&content)
+            .map_err(|e| with_chain(format!("Error parsing the toml file: {}", e), &new_chain))?;
+        let values = step.properties.get("params").and_then(|v| v.as_table());
+        let sub_params = params
+            .scoped(sub.params.as_ref(), values)
+            .map_err(|e| with_chain(e, &new_chain))?;
+        if let Some(sub_defaults) = &sub.params {
+            declared.extend(sub_defaults.keys().cloned());
+        }
+        let mut included = expand_includes(sub.steps, &sub_params, &new_chain, declared)?;
+        if let Some(input) = step.input {
+            match included.first_mut() {
+                Some(first) if first.input.is_none() => first.input = Some(input),
+                _ => {
+                    return Err(with_chain(
+                        String::from("include input needs a first included step without input"),
+                        &new_chain,
+                    ))
+                }
+            }
+        }
+        if let Some(name) = step.name {
+            match included.last_mut() {
+                Some(last) if last.name.is_none() => last.name = Some(name),
+                _ => {
+                    return Err(with_chain(
+                        String::from("include name needs a last included step without name"),
+                        &new_chain,
+                    ))
+                }
+            }
+        }
+        expanded.extend(included);
+    }
+    Ok(expanded)
+}
+
+fn canonical_path(path: &Path) -> Result<PathBuf, String> {
+    fs::canonicalize(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))
+}
+
+fn with_chain(message: String, chain: &[PathBuf]) -> String {
+    if chain.len() < 2 {
+        return message;
+    }
+    let files: Vec<String> = chain.iter().map(|p| p.display().to_string()).collect();
+    format!("{} (include chain: {})", message, files.join(" -> "))
+}
+
 /// Validates the Pipeline
 /// Validates the Pipeline struct according to the available set of operations
 /// This is an import aspect since the steps properties do not have a fixed contract that can be verified by the serializer
@@ -25,8 +138,9 @@ pub fn validate_pipeline(pipeline: &Pipeline) {
     let output = Vec::from(OUTPUT_OP);
     let input = Vec::from(INPUT_OP);
     let operations = Vec::from(OPERATIONS_OP);
+    let summary = Vec::from(SUMMARY_OP);
     // The general part is validated by the parser, but now we need to validate the operations
-    let all = [input, operations, output].concat();
+    let all = [input, operations, summary, output].concat();
 
     // Validate if all operations exist
     for step in &pipeline.steps {
@@ -38,6 +152,9 @@ pub fn validate_pipeline(pipeline: &Pipeline) {
         match step.operation.as_str() {
             "read_csv" => ReadCsvStep::validate(step),
             "preview" => PreviewStep::validate(step),
+            "save_csv" => SaveCsvStep::validate(step),
+            "split" => SplitStep::validate(step),
+            "explain" => ExplainStep::validate(step),
             "filter_eq" => FilterEqualStep::validate(step),
             "filter_gt_eq" => FilterGreaterEqStep::validate(step),
             "filter_lt" => FilterSmallerStep::validate(step),
@@ -51,9 +168,31 @@ pub fn validate_pipeline(pipeline: &Pipeline) {
             "select" => SelectColumnsStep::validate(step),
             "cast" => CastStep::validate(step),
             "anonymize" => AnonymizeStep::validate(step),
+            "mask_keep_last" => MaskKeepLastStep::validate(step),
+            "mask_email" => MaskEmailStep::validate(step),
+            "mask_regex" => MaskRegexStep::validate(step),
+            "mask_range" => MaskRangeStep::validate(step),
+            "mask_date" => MaskDateStep::validate(step),
             "pivot" => PivotStep::validate(step),
             "unique" => UniqueStep::validate(step),
+            "sample" => SampleStep::validate(step),
+            "describe" => DescribeStep::validate(step),
+            "value_counts" => ValueCountsStep::validate(step),
+            "crosstab" => CrosstabStep::validate(step),
+            "head" => HeadStep::validate(step),
+            "tail" => TailStep::validate(step),
+            "slice" => SliceStep::validate(step),
+            "window" => WindowStep::validate(step),
+            "rolling" => RollingStep::validate(step),
+            "resample" => ResampleStep::validate(step),
+            "bin" => BinStep::validate(step),
+            "encode" => EncodeStep::validate(step),
             _ => panic!("Step {} is not a valid operation", step.operation.as_str()),
         }
     }
-}
\ No newline at end of file
+
+    // Validate that every step reads an existing frame and that there are no cycles
+    if let Err(e) = PipelineGraph::from(pipeline) {
+        panic!("Error! {}", e);
+    }
+}
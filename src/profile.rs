@@ -0,0 +1,92 @@
+// oxidf profiling of the pipeline steps
+// Author: David Issa Mattos
+// Mantainer: David Issa Mattos
+
+use serde_derive::Serialize;
+use std::fs;
+use std::io;
+
+/// Profile of a single step
+/// The rows are only known when the frame is collected at the end of the step
+#[derive(Serialize, Clone)]
+pub struct StepProfile {
+    pub step: usize,
+    pub operation: String,
+    pub wall_time_ms: f64,
+    pub rows_in: Option<usize>,
+    pub rows_out: Option<usize>,
+    pub columns_in: Option<usize>,
+    pub columns_out: Option<usize>,
+    // peak memory of the process at the end of the step
+    pub peak_memory_kb: Option<u64>,
+}
+
+/// Profile of a pipeline run
+#[derive(Serialize)]
+pub struct PipelineProfile {
+    pub pipeline: String,
+    pub version: String,
+    pub wall_time_ms: f64,
+    pub peak_memory_kb: Option<u64>,
+    pub steps: Vec<StepProfile>,
+}
+impl PipelineProfile {
+    /// Print the profile as a table in stderr, so it does not mix with a csv written to stdout
+    pub fn print(&self) {
+        eprintln!(
+            "Profile of {}, version {}: {:.1} ms, peak memory {}",
+            self.pipeline,
+            self.version,
+            self.wall_time_ms,
+            format_memory(self.peak_memory_kb)
+        );
+        eprintln!(
+            "{:>4}  {:<16} {:>10} {:>10} {:>10} {:>8} {:>8} {:>12}",
+            "Step",
+            "Operation",
+            "Time (ms)",
+            "Rows in",
+            "Rows out",
+            "Cols in",
+            "Cols out",
+            "Peak memory"
+        );
+        for s in &self.steps {
+            eprintln!(
+                "{:>4}  {:<16} {:>10.1} {:>10} {:>10} {:>8} {:>8} {:>12}",
+                s.step,
+                s.operation,
+                s.wall_time_ms,
+                format_count(s.rows_in),
+                format_count(s.rows_out),
+                format_count(s.columns_in),
+                format_count(s.columns_out),
+                format_memory(s.peak_memory_kb)
+            );
+        }
+    }
+
+    /// Save the profile in a JSON file
+    pub fn save_json(&self, path: &str) -> Result<(), io::Error> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content)
+    }
+}
+
+/// Peak memory (resident set size) of the process in kB
+/// Only available on Linux, where it is read from /proc/self/status
+pub fn peak_memory_kb() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn format_count(value: Option<usize>) -> String {
+    value.map_or(String::from("-"), |v| v.to_string())
+}
+
+fn format_memory(value: Option<u64>) -> String {
+    value.map_or(String::from("-"), |kb| {
+        format!("{:.1} MB", kb as f64 / 1024.0)
+    })
+}
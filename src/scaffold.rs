@@ -0,0 +1,133 @@
+// oxidf scaffolding of new pipelines
+// Author: David Issa Mattos
+// Mantainer: David Issa Mattos
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+// delimiters we try when sniffing a csv file
+const DELIMITERS: &[char] = &[',', ';', '\t', '|', ' '];
+// number of lines read to sniff the csv file
+const SNIFF_LINES: usize = 50;
+
+/// Sniffed layout of a csv file
+pub struct CsvLayout {
+    pub delimiter: char,
+    pub header: bool,
+    pub columns: Vec<String>,
+}
+
+/// Sniff the delimiter, header and columns of a csv file
+/// The delimiter is the candidate that splits every line in the same (and largest) number of fields.
+/// The file is assumed to have a header when no field in the first line is a number
+pub fn sniff_csv(path: &str) -> Result<CsvLayout, String> {
+    let file = fs::File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+    let lines: Vec<String> = BufReader::new(file)
+        .lines()
+        .take(SNIFF_LINES)
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("Cannot read {}: {}", path, e))?
+        .into_iter()
+        .filter(|l| !l.trim().is_empty())
+        .collect();
+    let first = lines.first().ok_or(format!("{} is empty", path))?;
+
+    let mut delimiter = ',';
+    let mut n_fields = 1;
+    for candidate in DELIMITERS {
+        let counts: Vec<usize> = lines
+            .iter()
+            .map(|l| split_line(l, *candidate).len())
+            .collect();
+        let consistent = counts.iter().all(|c| *c == counts[0]);
+        if consistent && counts[0] > n_fields {
+            delimiter = *candidate;
+            n_fields = counts[0];
+        }
+    }
+
+    let fields = split_line(first, delimiter);
+    let header = fields
+        .iter()
+        .all(|f| !f.is_empty() && f.parse::<f64>().is_err());
+    let columns = match header {
+        true => fields,
+        // default names given by polars to files without header
+        false => (1..=fields.len())
+            .map(|i| format!("column_{}", i))
+            .collect(),
+    };
+    Ok(CsvLayout {
+        delimiter,
+        header,
+        columns,
+    })
+}
+
+/// Split a csv line in fields, ignoring delimiters inside quotes
+fn split_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.iter().map(|f| f.trim().to_string()).collect()
+}
+
+/// Create the TOML of a pipeline that reads a csv file, selects all its columns and previews it
+/// The path of the csv is written as an absolute path, since read_csv resolves relative paths
+/// against the directory where oxidf runs and not against the pipeline file
+pub fn scaffold_pipeline(path: &str) -> Result<String, String> {
+    let layout = sniff_csv(path)?;
+    let absolute = fs::canonicalize(path)
+        .map_err(|e| format!("Cannot open {}: {}", path, e))?
+        .to_string_lossy()
+        .to_string();
+    let name = Path::new(path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from("pipeline"));
+    let columns: Vec<String> = layout.columns.iter().map(|c| toml_string(c)).collect();
+    Ok(format!(
+        r#"[general]
+    name = {name}
+    version = "0.0.1"
+    mantainer = ""
+    description = {description}
+
+[[steps]]
+    operation = "read_csv"
+    [steps.properties]
+    path = {path}
+    delimiter = {delimiter}
+    header = {header}
+
+[[steps]]
+    operation = "select"
+    [steps.properties]
+    columns = [{columns}]
+
+[[steps]]
+    operation = "preview"
+    [steps.properties]
+"#,
+        name = toml_string(&name),
+        description = toml_string(&format!("Process {}", path)),
+        path = toml_string(&absolute),
+        delimiter = toml_string(&layout.delimiter.to_string()),
+        header = layout.header,
+        columns = columns.join(", "),
+    ))
+}
+
+/// A string written as a TOML value, quoted and escaped
+fn toml_string(s: &str) -> String {
+    toml::Value::String(s.to_string()).to_string()
+}
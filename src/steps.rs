@@ -1,11 +1,19 @@
 use polars::prelude::*;
 use serde_derive::Deserialize;
-use toml;
 use toml::value::Table;
 
 #[derive(Deserialize)]
 pub struct Pipeline {
     pub general: General,
+    // default values of the parameters that can be used in the properties as ${name}
+    pub params: Option<Table>,
+    pub steps: Vec<Steps>,
+}
+/// Pipeline included by another pipeline with an include step
+/// Only the parameters and the steps are used, the general section is optional
+#[derive(Deserialize)]
+pub struct SubPipeline {
+    pub params: Option<Table>,
     pub steps: Vec<Steps>,
 }
 #[derive(Deserialize)]
@@ -18,6 +26,10 @@ pub struct General {
 #[derive(Deserialize)]
 pub struct Steps {
     pub operation: String,
+    // optional name of the frame produced by this step, so that other steps can read it
+    pub name: Option<String>,
+    // optional name of the frame this step reads. Defaults to the frame of the previous step
+    pub input: Option<String>,
     pub properties: Table,
 }
 
@@ -0,0 +1,579 @@
+// oxidf summaries of the data
+// Author: David Issa Mattos
+// Mantainer: David Issa Mattos
+
+use crate::output::format_frame;
+use crate::steps::*;
+use crate::utils::*;
+use polars::prelude::*;
+use std::collections::HashMap;
+
+pub const SUMMARY_OP: &[&str] = &["describe", "value_counts", "crosstab"];
+
+/// Describe
+/// Descriptive statistics of every column, optionally for every group of rows.
+/// The result is a frame with one row per column (and group), so it can be saved with the output steps.
+/// The numeric columns get the mean, standard deviation, minimum, quartiles and maximum,
+/// the other columns get their most frequent values
+pub struct DescribeStep {
+    lazydf: LazyFrame,
+    columns: Option<Vec<String>>,
+    by: Vec<String>,
+    // number of most frequent values shown for the non-numeric columns
+    top: usize,
+    print: bool,
+}
+impl DescribeStep {
+    pub fn new(
+        lazydf: LazyFrame,
+        columns: Option<Vec<String>>,
+        by: Vec<String>,
+        top: usize,
+        print: bool,
+    ) -> Self {
+        DescribeStep {
+            lazydf,
+            columns,
+            by,
+            top,
+            print,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let columns = step.properties.get("columns").map(get_columns);
+        let by = step.properties.get("by").map_or(vec![], get_columns);
+        let top = step
+            .properties
+            .get("top")
+            .map_or(3, |t| t.as_integer().unwrap() as usize);
+        let print = step
+            .properties
+            .get("print")
+            .is_some_and(|p| p.as_bool().unwrap());
+        DescribeStep::new(lazydf, columns, by, top, print)
+    }
+}
+impl Execute for DescribeStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let df = self.lazydf.clone().collect()?;
+        let columns: Vec<String> = match &self.columns {
+            Some(columns) => columns.clone(),
+            None => df
+                .get_column_names()
+                .into_iter()
+                .filter(|c| !self.by.iter().any(|b| b == c))
+                .map(String::from)
+                .collect(),
+        };
+        let groups = match self.by.is_empty() {
+            true => vec![(0..df.height() as IdxSize).collect()],
+            false => group_rows(&df, &self.by)?,
+        };
+
+        let mut stats = DescribeColumns::default();
+        let mut keys: Vec<Vec<Option<String>>> = vec![vec![]; self.by.len()];
+        for rows in groups {
+            let rows = IdxCa::from_vec("rows", rows);
+            let group = df.take(&rows)?;
+            for c in &columns {
+                for (i, b) in self.by.iter().enumerate() {
+                    let key = group.column(b)?.cast(&DataType::Utf8)?;
+                    keys[i].push(key.utf8()?.get(0).map(String::from));
+                }
+                stats.push(group.column(c)?, self.top)?;
+            }
+        }
+
+        let mut series: Vec<Series> = self
+            .by
+            .iter()
+            .zip(keys)
+            .map(|(b, values)| Series::new(b, values))
+            .collect();
+        series.extend(stats.into_series());
+        let summary = DataFrame::new(series)?;
+        if self.print {
+            println!("{}", format_frame(&summary, summary.height(), None));
+        }
+        Ok(summary.lazy())
+    }
+    fn validate(step: &Steps) {
+        for name in ["columns", "by"] {
+            if let Some(value) = step.properties.get(name) {
+                assert!(
+                    is_columns(value),
+                    "Error! describe {} should be a column name or a non-empty list of column names",
+                    name
+                );
+            }
+        }
+        if let Some(top) = step.properties.get("top") {
+            assert!(
+                top.as_integer().is_some_and(|t| t >= 0),
+                "Error! describe top should be a non-negative integer"
+            );
+        }
+        if let Some(print) = step.properties.get("print") {
+            assert!(
+                print.is_bool(),
+                "Error! describe print should be true or false"
+            );
+        }
+    }
+}
+
+/// Statistics of the described columns, one value per column and group
+#[derive(Default)]
+struct DescribeColumns {
+    column: Vec<String>,
+    dtype: Vec<String>,
+    count: Vec<u32>,
+    null_count: Vec<u32>,
+    unique: Vec<u32>,
+    mean: Vec<Option<f64>>,
+    std: Vec<Option<f64>>,
+    min: Vec<Option<f64>>,
+    q25: Vec<Option<f64>>,
+    median: Vec<Option<f64>>,
+    q75: Vec<Option<f64>>,
+    max: Vec<Option<f64>>,
+    top: Vec<Option<String>>,
+}
+impl DescribeColumns {
+    fn push(&mut self, series: &Series, top: usize) -> Result<(), PolarsError> {
+        self.column.push(series.name().to_string());
+        self.dtype.push(series.dtype().to_string());
+        self.count.push((series.len() - series.null_count()) as u32);
+        self.null_count.push(series.null_count() as u32);
+        self.unique.push(series.n_unique()? as u32);
+        if series.dtype().is_numeric() {
+            let values = series.cast(&DataType::Float64)?;
+            let values = values.f64()?;
+            let quantile = |q: f64| values.quantile(q, QuantileInterpolOptions::Linear);
+            self.mean.push(values.mean());
+            self.std.push(values.std(1));
+            self.min.push(values.min());
+            self.q25.push(quantile(0.25)?);
+            self.median.push(quantile(0.5)?);
+            self.q75.push(quantile(0.75)?);
+            self.max.push(values.max());
+            self.top.push(None);
+        } else {
+            for stat in [
+                &mut self.mean,
+                &mut self.std,
+                &mut self.min,
+                &mut self.q25,
+                &mut self.median,
+                &mut self.q75,
+                &mut self.max,
+            ] {
+                stat.push(None);
+            }
+            self.top.push(Some(top_values(series, top)?));
+        }
+        Ok(())
+    }
+
+    fn into_series(self) -> Vec<Series> {
+        vec![
+            Series::new("column", self.column),
+            Series::new("dtype", self.dtype),
+            Series::new("count", self.count),
+            Series::new("null_count", self.null_count),
+            Series::new("unique", self.unique),
+            Series::new("mean", self.mean),
+            Series::new("std", self.std),
+            Series::new("min", self.min),
+            Series::new("25%", self.q25),
+            Series::new("50%", self.median),
+            Series::new("75%", self.q75),
+            Series::new("max", self.max),
+            Series::new("top", self.top),
+        ]
+    }
+}
+
+/// Most frequent values of a series with their frequencies, e.g. "setosa (50), virginica (48)"
+/// Ties are ordered by value
+fn top_values(series: &Series, top: usize) -> Result<String, PolarsError> {
+    let values = series.cast(&DataType::Utf8)?;
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for value in values.utf8()?.into_iter().flatten() {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+    let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    Ok(counts
+        .iter()
+        .take(top)
+        .map(|(value, count)| format!("{} ({})", value, count))
+        .collect::<Vec<String>>()
+        .join(", "))
+}
+
+/// Value counts
+/// Number of rows of every value of a column, optionally with their proportion.
+/// The values are sorted by count (or by value), and the least frequent values can be collapsed in a single category
+pub struct ValueCountsStep {
+    lazydf: LazyFrame,
+    col: String,
+    proportion: bool,
+    // count or value
+    sort: String,
+    max_categories: Option<usize>,
+    other: String,
+}
+impl ValueCountsStep {
+    pub fn new(
+        lazydf: LazyFrame,
+        col: String,
+        proportion: bool,
+        sort: String,
+        max_categories: Option<usize>,
+        other: String,
+    ) -> Self {
+        ValueCountsStep {
+            lazydf,
+            col,
+            proportion,
+            sort,
+            max_categories,
+            other,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let col = step
+            .properties
+            .get("col")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string();
+        let proportion = step
+            .properties
+            .get("proportion")
+            .is_some_and(|p| p.as_bool().unwrap());
+        let (sort, max_categories, other) = get_category_options(step);
+        ValueCountsStep::new(lazydf, col, proportion, sort, max_categories, other)
+    }
+}
+impl Execute for ValueCountsStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        let c = self.col.as_str();
+        // the most frequent values first, ties ordered by value
+        let mut counts = self
+            .lazydf
+            .clone()
+            .groupby([col(c)])
+            .agg([count().alias("count")])
+            .sort_by_exprs([col("count"), col(c)], [true, false], false);
+        if let Some(max) = self.max_categories {
+            // the stable groupby keeps the kept values in their order, followed by the other category
+            let (column, other) = (self.col.clone(), self.other.clone());
+            counts = counts
+                .with_row_count("rank", None)
+                .map(
+                    move |df| {
+                        if df.height() > max {
+                            let kept = df.head(Some(max));
+                            check_other(
+                                &column,
+                                kept.column(&column)?.cast(&DataType::Utf8)?.utf8()?,
+                                &other,
+                            )?;
+                        }
+                        Ok(df)
+                    },
+                    Some(AllowedOptimizations::default()),
+                    None,
+                    Some("value_counts other"),
+                )
+                .with_column(
+                    when(col("rank").lt(lit(max as IdxSize)))
+                        .then(col(c).cast(DataType::Utf8))
+                        .otherwise(lit(self.other.as_str()))
+                        .alias(c),
+                )
+                .groupby_stable([col(c)])
+                .agg([col("count").sum()]);
+        }
+        if self.sort == "value" {
+            counts = match self.max_categories {
+                Some(_) => counts.sort_by_exprs(
+                    [col(c).eq(lit(self.other.as_str())), col(c)],
+                    [false, false],
+                    false,
+                ),
+                None => counts.sort(c, SortOptions::default()),
+            };
+        }
+        if self.proportion {
+            counts = counts.with_column(
+                (col("count").cast(DataType::Float64) / col("count").sum().cast(DataType::Float64))
+                    .alias("proportion"),
+            );
+        }
+        Ok(counts)
+    }
+    fn validate(step: &Steps) {
+        assert!(
+            step.properties.get("col").is_some_and(|c| c.is_str()),
+            "Error! value_counts does not contain property: col"
+        );
+        if let Some(proportion) = step.properties.get("proportion") {
+            assert!(
+                proportion.is_bool(),
+                "Error! value_counts proportion should be true or false"
+            );
+        }
+        validate_category_options(step);
+    }
+}
+
+/// Crosstab
+/// Two-way table with the number of rows of every combination of values of two columns, optionally as proportions.
+/// The values of the first column are the rows of the table and the values of the second column its columns
+pub struct CrosstabStep {
+    lazydf: LazyFrame,
+    rows: String,
+    columns: String,
+    // all, row or column
+    proportion: Option<String>,
+    // count or value
+    sort: String,
+    max_categories: Option<usize>,
+    other: String,
+}
+impl CrosstabStep {
+    pub fn new(
+        lazydf: LazyFrame,
+        rows: String,
+        columns: String,
+        proportion: Option<String>,
+        sort: String,
+        max_categories: Option<usize>,
+        other: String,
+    ) -> Self {
+        CrosstabStep {
+            lazydf,
+            rows,
+            columns,
+            proportion,
+            sort,
+            max_categories,
+            other,
+        }
+    }
+    pub fn from(step: &Steps, lazydf: LazyFrame) -> Self {
+        let get_str = |name: &str| {
+            step.properties
+                .get(name)
+                .map(|v| v.as_str().unwrap().to_string())
+        };
+        let (sort, max_categories, other) = get_category_options(step);
+        CrosstabStep::new(
+            lazydf,
+            get_str("rows").unwrap(),
+            get_str("columns").unwrap(),
+            get_str("proportion"),
+            sort,
+            max_categories,
+            other,
+        )
+    }
+}
+impl Execute for CrosstabStep {
+    fn execute(&self) -> Result<LazyFrame, PolarsError> {
+        // the counts of every combination are small, so the table is built from them in memory
+        let counts = self
+            .lazydf
+            .clone()
+            .groupby([
+                col(&self.rows).cast(DataType::Utf8),
+                col(&self.columns).cast(DataType::Utf8),
+            ])
+            .agg([count().alias("count")])
+            .collect()?;
+        let row_values = counts.column(&self.rows)?.utf8()?;
+        let column_values = counts.column(&self.columns)?.utf8()?;
+        let n_rows = counts.column("count")?.cast(&DataType::UInt32)?;
+        let cells: Vec<(Option<&str>, Option<&str>, u32)> = row_values
+            .into_iter()
+            .zip(column_values)
+            .zip(n_rows.u32()?)
+            .map(|((r, c), n)| (r, c, n.unwrap_or(0)))
+            .collect();
+
+        let row_labels = self.categories(&self.rows, cells.iter().map(|(r, _, n)| (*r, *n)))?;
+        let column_labels =
+            self.categories(&self.columns, cells.iter().map(|(_, c, n)| (*c, *n)))?;
+        let mut table = vec![vec![0u32; column_labels.order.len()]; row_labels.order.len()];
+        for (r, c, n) in &cells {
+            table[row_labels.position(*r)][column_labels.position(*c)] += n;
+        }
+
+        let total: u32 = table.iter().flatten().sum();
+        let mut series = vec![Series::new(&self.rows, row_labels.order.clone())];
+        for (j, label) in column_labels.order.iter().enumerate() {
+            let name = label.clone().unwrap_or_else(|| String::from("null"));
+            // a value written as null, or named as the rows column, would give two columns with the same name
+            if series.iter().any(|s| s.name() == name) {
+                return Err(PolarsError::ComputeError(
+                    format!(
+                        "crosstab cannot name a column {:?} twice, rename the value {:?} of {} before the crosstab",
+                        name, name, self.columns
+                    )
+                    .into(),
+                ));
+            }
+            let counts: Vec<u32> = table.iter().map(|row| row[j]).collect();
+            let column = match self.proportion.as_deref() {
+                None => Series::new(&name, counts),
+                Some(proportion) => {
+                    let column_total: u32 = counts.iter().sum();
+                    let values: Vec<f64> = counts
+                        .iter()
+                        .zip(&table)
+                        .map(|(n, row)| {
+                            let denominator = match proportion {
+                                "row" => row.iter().sum(),
+                                "column" => column_total,
+                                _ => total,
+                            };
+                            *n as f64 / denominator.max(1) as f64
+                        })
+                        .collect();
+                    Series::new(&name, values)
+                }
+            };
+            series.push(column);
+        }
+        Ok(DataFrame::new(series)?.lazy())
+    }
+    fn validate(step: &Steps) {
+        for name in ["rows", "columns"] {
+            assert!(
+                step.properties.get(name).is_some_and(|c| c.is_str()),
+                "Error! crosstab does not contain property: {}",
+                name
+            );
+        }
+        if let Some(proportion) = step.properties.get("proportion") {
+            assert!(
+                proportion
+                    .as_str()
+                    .is_some_and(|p| ["all", "row", "column"].contains(&p)),
+                "Error! crosstab proportion should be all, row or column"
+            );
+        }
+        validate_category_options(step);
+    }
+}
+impl CrosstabStep {
+    /// Order of the values of one of the columns, with their totals, collapsing the least frequent ones
+    fn categories<'a>(
+        &self,
+        column: &str,
+        counts: impl Iterator<Item = (Option<&'a str>, u32)>,
+    ) -> Result<Categories, PolarsError> {
+        let mut totals: HashMap<Option<&str>, u32> = HashMap::new();
+        for (value, n) in counts {
+            *totals.entry(value).or_insert(0) += n;
+        }
+        let mut values: Vec<(Option<&str>, u32)> = totals.into_iter().collect();
+        values.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let kept = self
+            .max_categories
+            .unwrap_or(values.len())
+            .min(values.len());
+        let mut order: Vec<Option<String>> = values[..kept]
+            .iter()
+            .map(|(v, _)| v.map(String::from))
+            .collect();
+        if self.sort == "value" {
+            order.sort();
+        }
+        let collapsed = kept < values.len();
+        if collapsed {
+            check_other(column, values[..kept].iter().map(|(v, _)| *v), &self.other)?;
+            order.push(Some(self.other.clone()));
+        }
+        Ok(Categories { order, collapsed })
+    }
+}
+
+/// Values of a column of the crosstab, in their order
+struct Categories {
+    order: Vec<Option<String>>,
+    // were the least frequent values collapsed in the last category?
+    collapsed: bool,
+}
+impl Categories {
+    fn position(&self, value: Option<&str>) -> usize {
+        let kept = self.order.len() - self.collapsed as usize;
+        self.order[..kept]
+            .iter()
+            .position(|v| v.as_deref() == value)
+            .unwrap_or(kept)
+    }
+}
+
+// sort, maximum number of categories and name of the other category of value_counts and crosstab
+fn get_category_options(step: &Steps) -> (String, Option<usize>, String) {
+    let sort = step
+        .properties
+        .get("sort")
+        .map_or(String::from("count"), |s| s.as_str().unwrap().to_string());
+    let max_categories = step
+        .properties
+        .get("max_categories")
+        .map(|m| m.as_integer().unwrap() as usize);
+    let other = step
+        .properties
+        .get("other")
+        .map_or(String::from("Other"), |o| o.as_str().unwrap().to_string());
+    (sort, max_categories, other)
+}
+
+// a kept value with the name of the other category would be mixed with the collapsed values
+fn check_other<'a>(
+    column: &str,
+    kept: impl IntoIterator<Item = Option<&'a str>>,
+    other: &str,
+) -> Result<(), PolarsError> {
+    if kept.into_iter().any(|v| v == Some(other)) {
+        return Err(PolarsError::ComputeError(
+            format!(
+                "{} has a value {:?}, the name of the collapsed category. Give the collapsed category another name with the property other",
+                column, other
+            )
+            .into(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_category_options(step: &Steps) {
+    if let Some(sort) = step.properties.get("sort") {
+        assert!(
+            sort.as_str().is_some_and(|s| s == "count" || s == "value"),
+            "Error! {} sort should be count or value",
+            step.operation
+        );
+    }
+    if let Some(max_categories) = step.properties.get("max_categories") {
+        assert!(
+            max_categories.as_integer().is_some_and(|m| m > 0),
+            "Error! {} max_categories should be a positive integer",
+            step.operation
+        );
+    }
+    if let Some(other) = step.properties.get("other") {
+        assert!(
+            other.is_str(),
+            "Error! {} other should be a string",
+            step.operation
+        );
+    }
+}
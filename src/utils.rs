@@ -2,8 +2,12 @@
 // Author: David Issa Mattos
 // Mantainer: David Issa Mattos
 
+use hmac::{Hmac, Mac};
 use polars::{lazy::dsl::Expr, prelude::*};
-use sha2::{Digest, Sha256};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
 use toml::Value;
 
 pub fn get_int_array(value_array: Vec<Value>) -> Vec<i64> {
@@ -37,7 +41,7 @@ pub fn get_string_array(value_array: Vec<Value>) -> Vec<String> {
 pub fn get_float_array(value_array: Vec<Value>) -> Vec<f64> {
     let mut columns: Vec<f64> = vec![];
     for v in value_array {
-        let mut i: f64;
+        let i: f64;
         if v.is_float() {
             i = v.as_float().unwrap();
         } else if v.is_integer() {
@@ -51,6 +55,41 @@ pub fn get_float_array(value_array: Vec<Value>) -> Vec<f64> {
     columns
 }
 
+/// Value of a number property, that can be written as an integer or a float
+pub fn get_number(value: &Value) -> f64 {
+    match value {
+        Value::Integer(i) => *i as f64,
+        Value::Float(f) => *f,
+        _ => panic!("Error! {} is not a number", value),
+    }
+}
+
+/// Columns of a property that can be a single column or a list of columns
+pub fn get_columns(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(cols) => get_string_array(cols.clone()),
+        value => vec![value.as_str().unwrap().to_string()],
+    }
+}
+
+/// Is the property a column name or a non-empty list of column names?
+pub fn is_columns(value: &Value) -> bool {
+    match value {
+        Value::String(_) => true,
+        Value::Array(cols) => !cols.is_empty() && cols.iter().all(|c| c.is_str()),
+        _ => false,
+    }
+}
+
+/// Columns of a frame, in their order
+pub fn schema_columns(lazydf: &LazyFrame) -> Result<Vec<Expr>, PolarsError> {
+    Ok(lazydf
+        .schema()?
+        .iter_names()
+        .map(|name| col(name))
+        .collect())
+}
+
 pub fn get_array_columns(value_array: Vec<String>) -> Vec<Expr> {
     let mut select_cols: Vec<Expr> = vec![];
     for v in value_array {
@@ -59,31 +98,212 @@ pub fn get_array_columns(value_array: Vec<String>) -> Vec<Expr> {
     select_cols
 }
 
-fn hash(vin: String) -> String {
-    //Hash a string using Sha256
-    let mut hasher = Sha256::new();
-    hasher.update(vin.into_bytes());
-    let result = format!("{:X}", hasher.finalize());
-    result
+/// Hash algorithms available to anonymize
+pub const HASH_ALGORITHMS: &[&str] = &["sha256", "sha512", "blake3"];
+
+/// Settings used to hash (pseudonymize) values
+/// * algorithm: sha256, sha512 or blake3
+/// * salt: text added before every value, so the hashes cannot be found from a list of plausible values
+/// * key: secret key. When given the values are hashed with HMAC (keyed BLAKE3 for blake3)
+/// * length: number of characters kept from the hash
+#[derive(Clone)]
+pub struct Hasher {
+    pub algorithm: String,
+    pub salt: String,
+    pub key: Option<Vec<u8>>,
+    pub length: Option<usize>,
+}
+impl Default for Hasher {
+    fn default() -> Self {
+        Hasher {
+            algorithm: String::from("sha256"),
+            salt: String::new(),
+            key: None,
+            length: None,
+        }
+    }
+}
+impl Hasher {
+    /// Hash a single value, returning the hash as upper case hex
+    pub fn hash(&self, value: &str) -> String {
+        let input = format!("{}{}", self.salt, value);
+        let bytes: Vec<u8> = match (self.algorithm.as_str(), &self.key) {
+            ("sha256", None) => Sha256::digest(input.as_bytes()).to_vec(),
+            ("sha512", None) => Sha512::digest(input.as_bytes()).to_vec(),
+            ("blake3", None) => blake3::hash(input.as_bytes()).as_bytes().to_vec(),
+            ("sha256", Some(key)) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+                mac.update(input.as_bytes());
+                mac.finalize().into_bytes().to_vec()
+            }
+            ("sha512", Some(key)) => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(key).unwrap();
+                mac.update(input.as_bytes());
+                mac.finalize().into_bytes().to_vec()
+            }
+            ("blake3", Some(key)) => {
+                // keyed BLAKE3 needs a 32 bytes key, derived from the given key
+                let key = blake3::derive_key("oxidf anonymize", key);
+                blake3::keyed_hash(&key, input.as_bytes())
+                    .as_bytes()
+                    .to_vec()
+            }
+            (algorithm, _) => panic!("Error! {} is not a valid hash algorithm", algorithm),
+        };
+        let mut result: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        if let Some(length) = self.length {
+            result.truncate(length);
+        }
+        result
+    }
+
+    /// Hash the values of a series
+    /// Function that we can map lazily over a column
+    pub fn hash_series(&self, series: Series) -> Result<Series, PolarsError> {
+        map_utf8(series, |s| self.hash(s))
+    }
 }
 
-pub fn hash_polars(vin_series: &Series) -> Series {
-    //Function that we can apply to a element wise in polars
-    //https://pola-rs.github.io/polars/polars/frame/struct.DataFrame.html#method.apply
+/// Apply a function to every value of a series, returning a string series
+/// Series that are not strings are mapped from their string representation and null values are kept as null
+pub fn map_utf8<F>(series: Series, function: F) -> Result<Series, PolarsError>
+where
+    F: Fn(&str) -> String,
+{
     // Receives a series,
-    //convert it to Uf8 chunk,
-    // convert to iterator
-    // Realizes two maps (do not know why...)
+    // cast it to Utf8 and convert to iterator
+    // Maps the values that are not null
     // Converts back to a series after collecting
-
-    vin_series
-        .utf8()
-        .unwrap()
+    let name = series.name().to_string();
+    let mut mapped = series
+        .cast(&DataType::Utf8)?
+        .utf8()?
         .into_iter()
-        .map(|vin| vin.map(|s| hash(s.to_string())))
+        .map(|value| value.map(&function))
         .collect::<Utf8Chunked>()
-        .into_series()
+        .into_series();
+    mapped.rename(&name);
+    Ok(mapped)
+}
+
+/// Random number generator of the random operations
+/// The same seed always gives the same result, without seed the result changes in every run
+pub fn get_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
 
-    //use as
-    // df.apply("CarID",hash_polars);
+/// Indices of the rows of every combination of values of some columns, in the order the values first appear
+/// Null values form their own group
+pub fn group_rows(df: &DataFrame, group_cols: &[String]) -> Result<Vec<Vec<IdxSize>>, PolarsError> {
+    let values: Vec<Series> = group_cols
+        .iter()
+        .map(|c| df.column(c)?.cast(&DataType::Utf8))
+        .collect::<Result<_, _>>()?;
+    let values: Vec<&Utf8Chunked> = values.iter().map(|s| s.utf8()).collect::<Result<_, _>>()?;
+    let mut positions: HashMap<Vec<Option<&str>>, usize> = HashMap::new();
+    let mut groups: Vec<Vec<IdxSize>> = vec![];
+    for i in 0..df.height() {
+        let key: Vec<Option<&str>> = values.iter().map(|v| v.get(i)).collect();
+        let group = *positions.entry(key).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[group].push(i as IdxSize);
+    }
+    Ok(groups)
+}
+
+/// Is the text a duration such as 30s, 5m, 1h30m or 2d?
+/// Units: ns, us, ms, s, m, h, d, w, mo and y
+pub fn is_duration(text: &str) -> bool {
+    const UNITS: &[&str] = &["ns", "us", "ms", "s", "m", "h", "d", "w", "mo", "y"];
+    let mut rest = text;
+    while !rest.is_empty() {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 {
+            return false;
+        }
+        rest = &rest[digits..];
+        let letters = rest.len()
+            - rest
+                .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+                .len();
+        if !UNITS.contains(&&rest[..letters]) {
+            return false;
+        }
+        rest = &rest[letters..];
+    }
+    !text.is_empty()
+}
+
+/// Expression of a date, datetime or string column as a datetime (in microseconds)
+/// Strings are parsed with the format, which is guessed when not given
+pub fn datetime_column(
+    lazydf: &LazyFrame,
+    name: &str,
+    format: Option<String>,
+) -> Result<Expr, PolarsError> {
+    match lazydf.schema()?.get(name) {
+        Some(DataType::Utf8) => Ok(col(name).str().strptime(StrpTimeOptions {
+            date_dtype: DataType::Datetime(TimeUnit::Microseconds, None),
+            fmt: format,
+            strict: true,
+            exact: true,
+        })),
+        Some(_) => Ok(col(name).cast(DataType::Datetime(TimeUnit::Microseconds, None))),
+        None => Err(PolarsError::NotFound(name.to_string().into())),
+    }
+}
+
+/// Names of the data types of the columns
+pub const DATA_TYPES: &[&str] = &[
+    "string",
+    "int",
+    "float",
+    "int8",
+    "int16",
+    "int32",
+    "int64",
+    "uint8",
+    "uint16",
+    "uint32",
+    "uint64",
+    "float32",
+    "float64",
+    "bool",
+    "categorical",
+    "date",
+    "datetime",
+    "duration",
+];
+
+/// Data type from its name, with the time unit (ms, us or ns) of datetimes and durations
+pub fn get_dtype(name: &str, unit: &str) -> DataType {
+    let unit = match unit {
+        "ms" => TimeUnit::Milliseconds,
+        "ns" => TimeUnit::Nanoseconds,
+        _ => TimeUnit::Microseconds,
+    };
+    match name {
+        "string" => DataType::Utf8,
+        "int" | "int64" => DataType::Int64,
+        "float" | "float64" => DataType::Float64,
+        "int8" => DataType::Int8,
+        "int16" => DataType::Int16,
+        "int32" => DataType::Int32,
+        "uint8" => DataType::UInt8,
+        "uint16" => DataType::UInt16,
+        "uint32" => DataType::UInt32,
+        "uint64" => DataType::UInt64,
+        "float32" => DataType::Float32,
+        "bool" => DataType::Boolean,
+        "categorical" => DataType::Categorical(None),
+        "date" => DataType::Date,
+        "datetime" => DataType::Datetime(unit, None),
+        "duration" => DataType::Duration(unit),
+        _ => panic!("Error! {} is not a valid data type", name),
+    }
 }